@@ -0,0 +1,240 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// The elfcode instruction set shared by days 19 and 21.
+#[allow(non_camel_case_types)]
+#[derive(PartialEq, Eq, Hash, Deserialize, Clone, Debug)]
+pub(crate) enum OpCode {
+    addr,
+    addi,
+
+    mulr,
+    muli,
+
+    banr,
+    bani,
+
+    borr,
+    bori,
+
+    setr,
+    seti,
+
+    gtir,
+    gtri,
+    gtrr,
+
+    eqir,
+    eqri,
+    eqrr,
+}
+
+impl OpCode {
+    // Whether the A and B inputs are registers (as opposed to values).
+    pub(crate) fn register_inputs(&self) -> (bool, bool) {
+        use OpCode::*;
+        match self {
+            addr | mulr | banr | borr | gtrr | eqrr => (true, true),
+            addi | muli | bani | bori | setr | gtri | eqri => (true, false),
+            gtir | eqir => (false, true),
+            seti => (false, false),
+        }
+    }
+
+    pub(crate) fn run(&self, bench: &mut [usize; 6], a: usize, b: usize, c: usize) {
+        use OpCode::*;
+        match self {
+            // addr (add register) stores into register C the result of adding register A and register B.
+            addr => bench[c] = bench[a] + bench[b],
+            // addi (add immediate) stores into register C the result of adding register A and value B.
+            addi => bench[c] = bench[a] + b,
+
+            // mulr (multiply register) stores into register C the result of multiplying register A and register B.
+            mulr => bench[c] = bench[a] * bench[b],
+            // muli (multiply immediate) stores into register C the result of multiplying register A and value B.
+            muli => bench[c] = bench[a] * b,
+
+            // banr (bitwise AND register) stores into register C the result of the bitwise AND of register A and register B.
+            banr => bench[c] = bench[a] & bench[b],
+            // bani (bitwise AND immediate) stores into register C the result of the bitwise AND of register A and value B.
+            bani => bench[c] = bench[a] & b,
+
+            // borr (bitwise OR register) stores into register C the result of the bitwise OR of register A and register B.
+            borr => bench[c] = bench[a] | bench[b],
+            // bori (bitwise OR immediate) stores into register C the result of the bitwise OR of register A and value B.
+            bori => bench[c] = bench[a] | b,
+
+            // setr (set register) copies the contents of register A into register C. (Input B is ignored.)
+            setr => bench[c] = bench[a],
+            // seti (set immediate) stores value A into register C. (Input B is ignored.)
+            seti => bench[c] = a,
+
+            // gtir (greater-than immediate/register) sets register C to 1 if value A is greater than register B. Otherwise, register C is set to 0.
+            gtir => bench[c] = if a > bench[b] { 1 } else { 0 },
+            // gtri (greater-than register/immediate) sets register C to 1 if register A is greater than value B. Otherwise, register C is set to 0.
+            gtri => bench[c] = if bench[a] > b { 1 } else { 0 },
+            // gtrr (greater-than register/register) sets register C to 1 if register A is greater than register B. Otherwise, register C is set to 0.
+            gtrr => bench[c] = if bench[a] > bench[b] { 1 } else { 0 },
+
+            // eqir (equal immediate/register) sets register C to 1 if value A is equal to register B. Otherwise, register C is set to 0.
+            eqir => bench[c] = if a == bench[b] { 1 } else { 0 },
+            // eqri (equal register/immediate) sets register C to 1 if register A is equal to value B. Otherwise, register C is set to 0.
+            eqri => bench[c] = if bench[a] == b { 1 } else { 0 },
+            // eqrr (equal register/register) sets register C to 1 if register A is equal to register B. Otherwise, register C is set to 0.
+            eqrr => bench[c] = if bench[a] == bench[b] { 1 } else { 0 },
+        }
+    }
+}
+
+// Assembles elfcode with some conveniences into the plain `#ip` format:
+//
+//   #ip 5              ; the instruction pointer register, can be referred to as `ip` afterwards
+//   #reg counter 1     ; names register 1, registers can also be referred to as r0 to r5
+//   loop:              ; labels an instruction
+//     addi counter 1 counter
+//     gtri counter 9 r4
+//     addr r4 ip ip
+//     goto loop        ; sets the ip register to jump to the label
+//
+// Errors are reported with the (1-based) line they occur on.
+pub(crate) fn assemble(source: &str) -> Result<String, String> {
+    let mut ip_register = None;
+    let mut aliases = HashMap::new();
+    let mut labels = HashMap::new();
+    let mut instructions = Vec::new();
+
+    for (idx, line) in source.lines().enumerate() {
+        let at = |msg: String| format!("line {}: {}", idx + 1, msg);
+        let mut line = line.split(';').next().unwrap_or("").trim();
+
+        if let Some(directive) = line.strip_prefix('#') {
+            let parts = directive.split_ascii_whitespace().collect::<Vec<_>>();
+            match parts.as_slice() {
+                ["ip", register] => {
+                    if ip_register.is_some() {
+                        return Err(at("duplicate #ip directive".to_string()));
+                    }
+                    ip_register = Some(register_number(register, &aliases).map_err(at)?);
+                }
+                ["reg", name, register] => {
+                    let register = register_number(register, &aliases).map_err(at)?;
+                    if aliases.insert(name.to_string(), register).is_some() {
+                        return Err(at(format!("register {} is already named", name)));
+                    }
+                }
+                _ => return Err(at(format!("unknown directive #{}", directive))),
+            }
+            continue;
+        }
+
+        if let Some(colon) = line.find(':') {
+            let label = line[..colon].trim();
+            if label.is_empty() || label.contains(char::is_whitespace) {
+                return Err(at(format!("invalid label {:?}", label)));
+            }
+            if labels.insert(label.to_string(), instructions.len()).is_some() {
+                return Err(at(format!("duplicate label {}", label)));
+            }
+            line = line[colon + 1..].trim();
+        }
+
+        if !line.is_empty() {
+            instructions.push((idx + 1, line));
+        }
+    }
+
+    let ip_register = ip_register.ok_or_else(|| "missing #ip directive".to_string())?;
+    aliases.insert("ip".to_string(), ip_register);
+
+    let mut output = format!("#ip {}", ip_register);
+    for (line_number, line) in instructions {
+        let at = |msg: String| format!("line {}: {}", line_number, msg);
+        let parts = line.split_ascii_whitespace().collect::<Vec<_>>();
+        let lowered = match parts.as_slice() {
+            ["goto", label] => match labels.get(*label) {
+                None => return Err(at(format!("unknown label {}", label))),
+                // the ip is incremented after the instruction, so we have to land right before the label
+                Some(0) => return Err(at(format!("cannot goto {}, the ip register can't be set to the address before 0", label))),
+                Some(address) => format!("seti {} 0 {}", address - 1, ip_register),
+            },
+            [code, a, b, c] => {
+                let (a_is_register, b_is_register) = serde_plain::from_str::<OpCode>(code)
+                    .map_err(|_| at(format!("unknown instruction {}", code)))?
+                    .register_inputs();
+                let operand = |value: &str, is_register: bool| {
+                    if is_register {
+                        register_number(value, &aliases)
+                    } else {
+                        usize::from_str(value).map_err(|_| format!("invalid value {}", value))
+                    }
+                };
+                format!(
+                    "{} {} {} {}",
+                    code,
+                    operand(a, a_is_register).map_err(at)?,
+                    operand(b, b_is_register).map_err(at)?,
+                    register_number(c, &aliases).map_err(at)?
+                )
+            }
+            _ => return Err(at(format!("invalid instruction {}", line))),
+        };
+        output.push('\n');
+        output.push_str(&lowered);
+    }
+    Ok(output)
+}
+
+fn register_number(name: &str, aliases: &HashMap<String, usize>) -> Result<usize, String> {
+    let register = match aliases.get(name) {
+        Some(register) => *register,
+        None => usize::from_str(name.strip_prefix('r').unwrap_or(name)).map_err(|_| format!("unknown register {}", name))?,
+    };
+    if register < 6 {
+        Ok(register)
+    } else {
+        Err(format!("there are only 6 registers, but got {}", name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const COUNTER: &str = r#"
+; counts r0 up to 10
+#ip 5
+#reg counter 0
+
+    seti 0 0 r1     ; padding so that the loop doesn't start at 0
+loop:
+    addi counter 1 counter
+    gtri counter 9 r4
+    addr r4 ip ip   ; skips the goto once counter > 9
+    goto loop
+done: seti 99 0 r3
+"#;
+
+    #[test]
+    fn test_assemble() {
+        let expected = r#"#ip 5
+seti 0 0 1
+addi 0 1 0
+gtri 0 9 4
+addr 4 5 5
+seti 0 0 5
+seti 99 0 3"#;
+        assert_eq!(Ok(expected.to_string()), assemble(COUNTER));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Err("missing #ip directive".to_string()), assemble("seti 0 0 1"));
+        assert_eq!(Err("line 2: unknown label nowhere".to_string()), assemble("#ip 0\ngoto nowhere"));
+        assert_eq!(Err("line 3: cannot goto start, the ip register can't be set to the address before 0".to_string()), assemble("#ip 0\nstart: seti 0 0 1\ngoto start"));
+        assert_eq!(Err("line 2: unknown register counter".to_string()), assemble("#ip 0\naddi counter 1 1"));
+        assert_eq!(Err("line 2: there are only 6 registers, but got r6".to_string()), assemble("#ip 0\nsetr r6 0 1"));
+        assert_eq!(Err("line 2: unknown instruction jmp".to_string()), assemble("#ip 0\njmp 1 2 3"));
+        assert_eq!(Err("line 3: duplicate label a".to_string()), assemble("#ip 0\na: seti 0 0 1\na: seti 0 0 1"));
+    }
+}
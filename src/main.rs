@@ -1,5 +1,6 @@
 use structopt::StructOpt;
 
+mod elfcode;
 mod puzzle1;
 mod puzzle2;
 mod puzzle3;
@@ -29,12 +30,20 @@ mod puzzle25;
 pub trait Puzzle {
     fn part1(&self) -> String;
     fn part2(&self) -> String;
+
+    // Puzzle specific questions about the input, beyond the two parts.
+    fn query(&self, query: &str) -> Result<String, String> {
+        Err(format!("this puzzle doesn't answer queries, got {}", query))
+    }
 }
 
 #[derive(StructOpt)]
 struct Cli {
     puzzle: Option<usize>,
-    part: Option<u32>
+    part: Option<u32>,
+    /// Asks the puzzle a question about its input instead of solving it, e.g.: `16 --query opcodes`
+    #[structopt(long)]
+    query: Option<String>,
 }
 
 fn main() {
@@ -84,6 +93,13 @@ fn main() {
         let ref mk_puzzle = puzzles[pzl-1];
         let input = std::fs::read_to_string(format!("src/puzzle{}/input.txt", pzl)).expect("cannot read puzzle input.");
         let puzzle = mk_puzzle(input);
+        if let Some(query) = &args.query {
+            match puzzle.query(query) {
+                Ok(answer) => println!("Puzzle {} {}:\n{}", pzl, query, answer),
+                Err(err) => eprintln!("Puzzle {} {}: {}", pzl, query, err),
+            }
+            continue;
+        }
         for part in parts.clone() {
             let result = match part {
                 1 => puzzle.part1(),
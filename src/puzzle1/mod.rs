@@ -1,48 +1,182 @@
-use std::collections::HashSet;
-
-fn parse(input: String) -> Vec<i32> {
-    input.lines()
-        .filter(|s| !s.is_empty())
-        .map(|s| {
-            let mut st = String::from(s);
-            let first = st.remove(0);
-            let value = st.parse::<i32>().expect("not an int");
-            match first {
-                '-' => value * -1,
-                '+' => value,
-                c => panic!("unexpected char in input {}", c)
-            }
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+// Parses frequency changes lazily from any lines, e.g.: `+1`, `-2`, `3`, `+1, -2, +3` or `1 -2 3`.
+fn parse<I, S>(lines: I) -> impl Iterator<Item = i64>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    lines.into_iter().flat_map(|line| {
+        line.as_ref()
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                let value = s.strip_prefix('+').unwrap_or(s);
+                i64::from_str(value).unwrap_or_else(|_| panic!("invalid frequency change {}", s))
+            })
+            .collect::<Vec<_>>()
+    })
+}
+
+#[derive(PartialEq, Debug)]
+struct Repeat {
+    value: i64,
+    // the pass over the changes (starting at 0) and the index of the change that produced the repeated frequency
+    iteration: u64,
+    position: usize,
+}
+
+// Finds the first frequency reached twice without cycling through the changes.
+//
+// After k full passes, the frequency before applying change j is k * drift + prefix[j].
+// Two of those are equal only when their prefix sums are congruent modulo the drift, so within each residue class
+// the earliest repeat comes from catching up to the nearest prefix sum in the direction of the drift.
+//
+// Only the prefix sums are kept, so the changes can come straight from the input.
+fn first_repeat(changes: impl IntoIterator<Item = i64>) -> Option<Repeat> {
+    let mut drift = 0i64;
+    let prefix = changes
+        .into_iter()
+        .map(|inc| {
+            let before = drift;
+            drift += inc;
+            before
+        })
+        .collect::<Vec<_>>();
+    let n = prefix.len();
+
+    // "time" t is the index of a frequency in the sequence that starts with the initial 0, it's produced by change t - 1
+    let repeat_at = |t: u64, value: i64| Repeat { value, iteration: (t - 1) / n as u64, position: ((t - 1) % n as u64) as usize };
+
+    // a frequency repeated within the first pass comes before anything else
+    let mut seen = HashSet::new();
+    if let Some(j) = prefix.iter().position(|freq| !seen.insert(*freq)) {
+        return Some(repeat_at(j as u64, prefix[j]));
+    }
+    if n == 0 {
+        return None;
+    }
+    if drift == 0 {
+        // the second pass starts over from 0
+        return Some(repeat_at(n as u64, 0));
+    }
+
+    let mut classes: HashMap<i64, Vec<usize>> = HashMap::new();
+    for (j, freq) in prefix.iter().enumerate() {
+        classes.entry(freq.rem_euclid(drift.abs())).or_default().push(j);
+    }
+
+    classes
+        .values_mut()
+        .flat_map(|class| {
+            // ordered in the direction of the drift, each frequency is caught up with by the previous one
+            class.sort_by_key(|j| prefix[*j] * drift.signum());
+            class
+                .windows(2)
+                .map(|pair| {
+                    let (from, to) = (pair[0], pair[1]);
+                    let passes = ((prefix[to] - prefix[from]) / drift) as u64;
+                    (passes * n as u64 + from as u64, prefix[to])
+                })
+                .collect::<Vec<_>>()
         })
-        .collect()
+        .min()
+        .map(|(t, value)| repeat_at(t, value))
 }
 
 pub fn mk(input: String) -> Box<dyn crate::Puzzle> {
-    Box::new(Puzzle1 { input: parse(input) })
+    Box::new(Puzzle1 { input })
 }
 
 pub struct Puzzle1 {
-    input: Vec<i32>
+    input: String
 }
 
 impl crate::Puzzle for Puzzle1 {
 
     fn part1(&self) -> String {
-        self.input.iter().sum::<i32>().to_string()
+        parse(self.input.lines()).sum::<i64>().to_string()
     }
 
     fn part2(&self) -> String {
-        let mut seen_freqs = HashSet::new();
-        seen_freqs.insert(0);
-
-        self.input
-            .iter()
-            .cycle()
-            .scan(0, |freq, inc| {
-                *freq = *freq + inc; // NOTE: we're mutating the internal state here. The state of scan isn't an accumulator like fold.
-                Some(*freq)
-            })
-            .find(|x| !seen_freqs.insert(*x))
-            .expect("couldn't find a freq")
-            .to_string()
+        match first_repeat(parse(self.input.lines())) {
+            None => "no repeat".to_string(),
+            Some(repeat) => repeat.value.to_string(),
+        }
+    }
+
+    fn query(&self, query: &str) -> Result<String, String> {
+        match query {
+            "repeat" => Ok(match first_repeat(parse(self.input.lines())) {
+                None => "no frequency is reached twice".to_string(),
+                Some(Repeat { value, iteration, position }) => {
+                    format!("frequency {} is reached twice by change #{} on iteration {}", value, position, iteration)
+                }
+            }),
+            _ => Err(format!("unknown query {}, expected: repeat", query)),
+        }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_examples() {
+        assert_eq!(Some(0), first_repeat(vec![1, -1]).map(|r| r.value));
+        assert_eq!(Some(10), first_repeat(vec![3, 3, 4, -2, -4]).map(|r| r.value));
+        assert_eq!(Some(5), first_repeat(vec![-6, 3, 8, 5, -6]).map(|r| r.value));
+        assert_eq!(Some(14), first_repeat(vec![7, 7, -2, -7, -4]).map(|r| r.value));
+    }
+
+    #[test]
+    fn test_position() {
+        // 0, 3, 6, 10, 8, 4, 7, 10
+        assert_eq!(Some(Repeat { value: 10, iteration: 1, position: 1 }), first_repeat(vec![3, 3, 4, -2, -4]));
+        // 0, 1, 0
+        assert_eq!(Some(Repeat { value: 0, iteration: 0, position: 1 }), first_repeat(vec![1, -1]));
+        // 0, 2, 1, 3, 1
+        assert_eq!(Some(Repeat { value: 1, iteration: 0, position: 3 }), first_repeat(vec![2, -1, 2, -2]));
+    }
+
+    #[test]
+    fn test_no_repeat() {
+        assert_eq!(None, first_repeat(vec![1, 2, 3]));
+        assert_eq!(None, first_repeat(vec![]));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(vec![1, -2, 3], parse(vec!["+1", "-2", "+3"]).collect::<Vec<_>>());
+        assert_eq!(vec![1, -2, 3, 4, 5], parse(vec!["1, -2,+3", "", "  4 5 "]).collect::<Vec<_>>());
+        assert_eq!(vec![3_000_000_000, -3_000_000_000], parse("+3000000000\n-3000000000".lines()).collect::<Vec<_>>());
+        assert_eq!(0, parse(vec!["+3000000000 -3000000000"]).sum::<i64>());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid frequency change +x")]
+    fn test_parse_invalid() {
+        parse(vec!["+1, +x"]).for_each(drop);
+    }
+
+    #[test]
+    fn test_streaming() {
+        // a million lines that are never collected
+        let lines = (0..1_000_000).map(|i| if i % 2 == 0 { "+2".to_string() } else { "-1".to_string() });
+        assert_eq!(500_000, parse(lines).sum::<i64>());
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        let brute_force = |changes: &Vec<i64>| {
+            let mut seen = HashSet::new();
+            seen.insert(0);
+            changes.iter().cycle().scan(0, |freq, inc| { *freq += inc; Some(*freq) }).find(|x| !seen.insert(*x)).unwrap()
+        };
+        for changes in vec![parse(include_str!("input.txt").lines()).collect(), vec![5, -3, 7, -8], vec![-5, 3, -7, 8]] {
+            assert_eq!(Some(brute_force(&changes)), first_repeat(changes).map(|r| r.value));
+        }
+    }
+}
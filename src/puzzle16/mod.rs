@@ -2,10 +2,11 @@ use std::str::FromStr;
 use itertools::Itertools;
 use regex::Regex;
 use std::ops::{Index, IndexMut};
-use std::collections::{HashSet, HashMap};
+use std::collections::{HashSet, HashMap, BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
 
 #[allow(non_camel_case_types)]
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Debug)]
 enum OpCode {
     addr,
     addi,
@@ -217,51 +218,135 @@ impl Valid {
     }
 }
 
-fn resolve(unassigned: &HashMap<u8, HashSet<OpCode>>, assigned: &HashMap<u8, OpCode>) -> Option<HashMap<u8, OpCode>> {
-    // base cases
-    //   unassigned is empty
-    //   unassigned has an empty set
+#[derive(PartialEq, Eq, Debug)]
+enum Step {
+    // the code has a single candidate left
+    Single(u8, OpCode),
+    // the opcode is a candidate of a single code, every opcode has to be assigned to some code
+    Exclusive(u8, OpCode),
+}
+
+#[derive(PartialEq, Eq, Debug)]
+enum Outcome {
+    Unique(HashMap<u8, OpCode>),
+    // two of the possible mappings
+    Ambiguous(HashMap<u8, OpCode>, HashMap<u8, OpCode>),
+    // the codes that can't be assigned and the (0-based) samples responsible for it
+    Contradiction { codes: Vec<u8>, samples: Vec<usize> },
+}
 
-    if unassigned.is_empty() { Some(assigned.clone()) }
-    else if unassigned.iter().find(|(_, possible)| possible.is_empty()).is_some() { None }
-    else {
-        // pick the next code and try one assignment from its possible assignments
-        let mut remains = unassigned.iter().collect::<Vec<_>>();
-        remains.sort_by_key(|(_, possible)| possible.len());
+// Explains how codes are assigned to opcodes.
+#[derive(PartialEq, Eq, Debug)]
+struct Inference {
+    // the opcodes matching all samples of each code
+    candidates: BTreeMap<u8, BTreeSet<OpCode>>,
+    // the assignments made by propagating constraints, before having to search
+    steps: Vec<Step>,
+    outcome: Outcome,
+}
 
-        for (code, possible) in remains {
+impl Inference {
+    fn mapping(&self) -> Option<&HashMap<u8, OpCode>> {
+        match &self.outcome {
+            Outcome::Unique(mapping) | Outcome::Ambiguous(mapping, _) => Some(mapping),
+            Outcome::Contradiction { .. } => None,
+        }
+    }
+}
+
+impl Display for Inference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let names = |opcodes: &BTreeSet<OpCode>| opcodes.iter().map(|o| format!("{:?}", o)).join(" ");
+        let mapping = |mapping: &HashMap<u8, OpCode>| {
+            mapping.iter().sorted_by_key(|(code, _)| **code).map(|(code, opcode)| format!("{}={:?}", code, opcode)).join(" ")
+        };
+
+        writeln!(f, "candidates:")?;
+        for (code, opcodes) in &self.candidates {
+            writeln!(f, "  {:>2}: {}", code, names(opcodes))?;
+        }
+        writeln!(f, "propagation:")?;
+        for step in &self.steps {
+            match step {
+                Step::Single(code, opcode) => writeln!(f, "  {:>2} => {:?} (only candidate left)", code, opcode)?,
+                Step::Exclusive(code, opcode) => writeln!(f, "  {:>2} => {:?} (no other code can be {:?})", code, opcode, opcode)?,
+            }
+        }
+        match &self.outcome {
+            Outcome::Unique(m) => write!(f, "unique mapping: {}", mapping(m)),
+            Outcome::Ambiguous(a, b) => write!(f, "ambiguous mapping, for example:\n  {}\n  {}", mapping(a), mapping(b)),
+            Outcome::Contradiction { codes, samples } => write!(
+                f,
+                "no mapping, codes {} contradict each other in samples {}",
+                codes.iter().join(", "),
+                samples.iter().map(|s| format!("#{}", s + 1)).join(", ")
+            ),
+        }
+    }
+}
+
+// Finds the samples that together pin a code to its remaining candidates, possibly none: the sample that narrowed them down
+//   to the final set, along with the earlier ones needed to get there.
+fn contradicting_samples(input: &Vec<Valid>, code: u8) -> Vec<usize> {
+    let samples = input.iter().enumerate().filter(|(_, valid)| valid.instruction.code == code).collect::<Vec<_>>();
+    let remaining = samples.iter().fold(OpCode::all().into_iter().collect::<HashSet<_>>(), |possible, (_, valid)| &possible & &valid.matching_opcodes());
+    let mut possible = OpCode::all().into_iter().collect::<HashSet<_>>();
+    let last = samples.iter().position(|(_, valid)| {
+        possible = &possible & &valid.matching_opcodes();
+        possible == remaining
+    });
+    let last = match last {
+        None => return Vec::new(),
+        Some(last) => last,
+    };
+
+    let (idx, valid) = samples[last];
+    let mut contradicting = vec![idx];
+    let mut possible = valid.matching_opcodes();
+    for (idx, valid) in samples[..last].iter().rev() {
+        if possible == remaining {
+            break;
+        }
+        let narrowed = &possible & &valid.matching_opcodes();
+        if narrowed.len() < possible.len() {
+            contradicting.push(*idx);
+            possible = narrowed;
+        }
+    }
+    contradicting.sort();
+    contradicting
+}
+
+// Finds up to `limit` mappings by backtracking over the remaining candidates.
+fn search(unassigned: &BTreeMap<u8, BTreeSet<OpCode>>, assigned: &mut HashMap<u8, OpCode>, limit: usize, found: &mut Vec<HashMap<u8, OpCode>>) {
+    if found.len() >= limit {
+        return;
+    }
+    let next = unassigned.iter().filter(|(code, _)| !assigned.contains_key(code)).min_by_key(|(_, possible)| possible.len());
+    match next {
+        None => found.push(assigned.clone()),
+        Some((code, possible)) => {
             for opcode in possible {
-                let mut new_assignment = assigned.clone();
-                new_assignment.insert(*code, opcode.clone());
-                let new_unassigned = unassigned.clone()
-                    .iter_mut()
-                    .filter(|(other, _)| *other != code)
-                    .map(|(code, possible)| {
-                        possible.remove(opcode);
-                        (*code, possible.clone())
-                    })
-                    .collect::<HashMap<u8, HashSet<OpCode>>>();
-
-                match resolve(&new_unassigned, &new_assignment) {
-                    None => (),
-                    sol@Some(_) => return sol
-                };
+                if !assigned.values().any(|o| o == opcode) {
+                    assigned.insert(*code, opcode.clone());
+                    search(unassigned, assigned, limit, found);
+                    assigned.remove(code);
+                }
             }
         }
-        None
     }
 }
 
-fn resolve_opcodes(input: &Vec<Valid>) -> Option<HashMap<u8, OpCode>> {
-    let mut possible = HashMap::new();
+fn infer_opcodes(input: &Vec<Valid>) -> Inference {
+    let mut candidates: BTreeMap<u8, BTreeSet<OpCode>> = BTreeMap::new();
     input
         .iter()
         .for_each(|valid| {
             let matching = valid.matching_opcodes();
 
-            match possible.get_mut(&valid.instruction.code) {
+            match candidates.get_mut(&valid.instruction.code) {
                 None => {
-                    possible.insert(valid.instruction.code, matching);
+                    candidates.insert(valid.instruction.code, matching.into_iter().collect());
                 },
                 Some(current) => {
                     current.retain(|opcode| matching.contains(opcode));
@@ -269,7 +354,67 @@ fn resolve_opcodes(input: &Vec<Valid>) -> Option<HashMap<u8, OpCode>> {
             };
         });
 
-    resolve(&possible, &HashMap::new())
+    let contradiction = |codes: Vec<u8>| {
+        let samples = codes.iter().flat_map(|code| contradicting_samples(input, *code)).sorted().dedup().collect();
+        Outcome::Contradiction { codes, samples }
+    };
+
+    let empty = candidates.iter().filter(|(_, possible)| possible.is_empty()).map(|(code, _)| *code).collect::<Vec<_>>();
+    if !empty.is_empty() {
+        let outcome = contradiction(empty);
+        return Inference { candidates, steps: Vec::new(), outcome };
+    }
+
+    // propagate the assignments that are forced
+    let every_opcode_used = candidates.len() == OpCode::all().len();
+    let mut remaining = candidates.clone();
+    let mut assigned = HashMap::new();
+    let mut steps = Vec::new();
+    loop {
+        let single = remaining
+            .iter()
+            .find(|(_, possible)| possible.len() == 1)
+            .map(|(code, possible)| Step::Single(*code, possible.iter().next().unwrap().clone()));
+        let exclusive = || {
+            OpCode::all().into_iter().find_map(|opcode| {
+                let mut codes = remaining.iter().filter(|(_, possible)| possible.contains(&opcode));
+                match (codes.next(), codes.next()) {
+                    (Some((code, _)), None) if every_opcode_used => Some(Step::Exclusive(*code, opcode)),
+                    _ => None,
+                }
+            })
+        };
+        let step = match single.or_else(exclusive) {
+            None => break,
+            Some(step) => step,
+        };
+        let (code, opcode) = match &step {
+            Step::Single(code, opcode) | Step::Exclusive(code, opcode) => (*code, opcode.clone()),
+        };
+        steps.push(step);
+        remaining.remove(&code);
+
+        // this code took the last candidate of other codes
+        let starved = remaining.iter().filter(|(_, possible)| possible.len() == 1 && possible.contains(&opcode)).map(|(code, _)| *code).collect::<Vec<_>>();
+        if !starved.is_empty() {
+            let outcome = contradiction(std::iter::once(code).chain(starved).sorted().collect());
+            return Inference { candidates, steps, outcome };
+        }
+        remaining.values_mut().for_each(|possible| { possible.remove(&opcode); });
+        assigned.insert(code, opcode);
+    }
+
+    let mut found = Vec::new();
+    search(&remaining, &mut assigned, 2, &mut found);
+    let outcome = match found.len() {
+        0 => contradiction(remaining.keys().cloned().collect()),
+        1 => Outcome::Unique(found.pop().unwrap()),
+        _ => {
+            let second = found.pop().unwrap();
+            Outcome::Ambiguous(found.pop().unwrap(), second)
+        }
+    };
+    Inference { candidates, steps, outcome }
 }
 
 fn parse(input: &str) -> (Vec<Valid>, Vec<Instr>) {
@@ -331,15 +476,23 @@ impl crate::Puzzle for Puzzle16 {
     }
 
     fn part2(&self) -> String {
-        match resolve_opcodes(&self.part1) {
-            None => panic!("couldn't assign codes to opcodes."),
+        let inference = infer_opcodes(&self.part1);
+        match inference.mapping() {
+            None => panic!("couldn't assign codes to opcodes.\n{}", inference),
             Some(assignement) => {
-                let mut cpu = Cpu::new(&assignement);
+                let mut cpu = Cpu::new(assignement);
                 cpu.run(&self.part2);
                 cpu.bench[&0].to_string()
             }
         }
     }
+
+    fn query(&self, query: &str) -> Result<String, String> {
+        match query {
+            "opcodes" => Ok(infer_opcodes(&self.part1).to_string()),
+            _ => Err(format!("unknown query {}, expected: opcodes", query)),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -386,4 +539,68 @@ After:  [3, 2, 2, 1]
         let valid = Valid::from(&PART1_EXAMPLE.lines().collect());
         assert_eq!(HashSet::from_iter(vec![OpCode::addi, OpCode::mulr, OpCode::seti]), valid.matching_opcodes());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_infer_unique() {
+        let (part1, _) = parse(include_str!("input.txt"));
+        let inference = infer_opcodes(&part1);
+        assert_eq!(16, inference.steps.len());
+        match inference.outcome {
+            Outcome::Unique(mapping) => assert_eq!(Some(&OpCode::bani), mapping.get(&0)),
+            other => panic!("expected a unique mapping, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_infer_ambiguous() {
+        let samples = vec![
+            Valid::from(&PART1_EXAMPLE.lines().collect()),
+            Valid::from(&PART1_EXAMPLE.replace("9 2 1 2", "5 2 1 2").lines().collect()),
+        ];
+        let inference = infer_opcodes(&samples);
+        assert_eq!(BTreeSet::from_iter(vec![OpCode::addi, OpCode::mulr, OpCode::seti]), inference.candidates[&5]);
+        assert!(inference.steps.is_empty());
+        match inference.outcome {
+            Outcome::Ambiguous(a, b) => assert_ne!(a, b),
+            other => panic!("expected an ambiguous mapping, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_infer_contradiction() {
+        let samples = vec![
+            Valid::from(&PART1_EXAMPLE.lines().collect()),
+            Valid::from(&PART1_EXAMPLE.lines().collect()),
+            Valid::from(&vec!["Before: [0, 0, 0, 0]", "9 0 0 0", "After:  [1, 0, 0, 0]"]),
+        ];
+        let inference = infer_opcodes(&samples);
+        assert_eq!(Outcome::Contradiction { codes: vec![9], samples: vec![1, 2] }, inference.outcome);
+        assert!(inference.to_string().ends_with("no mapping, codes 9 contradict each other in samples #2, #3"));
+        assert_eq!(None, inference.mapping());
+    }
+
+    #[test]
+    fn test_infer_starved() {
+        // only mulr gives 6, while both addr and mulr give 4
+        let mulr = |code: &str| Valid::from(&vec!["Before: [2, 3, 0, 0]", code, "After:  [2, 3, 6, 0]"]);
+        let samples = vec![
+            mulr("9 0 1 2"),
+            Valid::from(&vec!["Before: [2, 2, 0, 0]", "7 0 1 2", "After:  [2, 2, 4, 0]"]),
+            mulr("7 0 1 2"),
+            mulr("9 0 1 2"),
+        ];
+        let inference = infer_opcodes(&samples);
+        assert_eq!(Outcome::Contradiction { codes: vec![7, 9], samples: vec![0, 2] }, inference.outcome);
+        assert_eq!(None, inference.mapping());
+    }
+
+    #[test]
+    fn test_infer_pigeonhole() {
+        // three codes sharing the same two candidates
+        let addr_or_mulr = |code: &str| Valid::from(&vec!["Before: [2, 2, 0, 0]", code, "After:  [2, 2, 4, 0]"]);
+        let samples = vec![addr_or_mulr("1 0 1 2"), addr_or_mulr("2 0 1 2"), addr_or_mulr("2 0 1 2"), addr_or_mulr("3 0 1 2")];
+        let inference = infer_opcodes(&samples);
+        assert_eq!(Outcome::Contradiction { codes: vec![1, 2, 3], samples: vec![0, 1, 3] }, inference.outcome);
+        assert_eq!(None, inference.mapping());
+    }
+}
@@ -1,79 +1,8 @@
 use regex::Regex;
-use serde::Deserialize;
+use std::fs;
 use std::str::FromStr;
 
-#[allow(non_camel_case_types)]
-#[derive(PartialEq, Eq, Hash, Deserialize, Clone, Debug)]
-enum OpCode {
-    addr,
-    addi,
-
-    mulr,
-    muli,
-
-    banr,
-    bani,
-
-    borr,
-    bori,
-
-    setr,
-    seti,
-
-    gtir,
-    gtri,
-    gtrr,
-
-    eqir,
-    eqri,
-    eqrr,
-}
-
-impl OpCode {
-    fn run(&self, bench: &mut [usize; 6], a: usize, b: usize, c: usize) {
-        use OpCode::*;
-        match self {
-            // addr (add register) stores into register C the result of adding register A and register B.
-            addr => bench[c] = bench[a] + bench[b],
-            // addi (add immediate) stores into register C the result of adding register A and value B.
-            addi => bench[c] = bench[a] + b,
-
-            // mulr (multiply register) stores into register C the result of multiplying register A and register B.
-            mulr => bench[c] = bench[a] * bench[b],
-            // muli (multiply immediate) stores into register C the result of multiplying register A and value B.
-            muli => bench[c] = bench[a] * b,
-
-            // banr (bitwise AND register) stores into register C the result of the bitwise AND of register A and register B.
-            banr => bench[c] = bench[a] & bench[b],
-            // bani (bitwise AND immediate) stores into register C the result of the bitwise AND of register A and value B.
-            bani => bench[c] = bench[a] & b,
-
-            // borr (bitwise OR register) stores into register C the result of the bitwise OR of register A and register B.
-            borr => bench[c] = bench[a] | bench[b],
-            // bori (bitwise OR immediate) stores into register C the result of the bitwise OR of register A and value B.
-            bori => bench[c] = bench[a] | b,
-
-            // setr (set register) copies the contents of register A into register C. (Input B is ignored.)
-            setr => bench[c] = bench[a],
-            // seti (set immediate) stores value A into register C. (Input B is ignored.)
-            seti => bench[c] = a,
-
-            // gtir (greater-than immediate/register) sets register C to 1 if value A is greater than register B. Otherwise, register C is set to 0.
-            gtir => bench[c] = if a > bench[b] { 1 } else { 0 },
-            // gtri (greater-than register/immediate) sets register C to 1 if register A is greater than value B. Otherwise, register C is set to 0.
-            gtri => bench[c] = if bench[a] > b { 1 } else { 0 },
-            // gtrr (greater-than register/register) sets register C to 1 if register A is greater than register B. Otherwise, register C is set to 0.
-            gtrr => bench[c] = if bench[a] > bench[b] { 1 } else { 0 },
-
-            // eqir (equal immediate/register) sets register C to 1 if value A is equal to register B. Otherwise, register C is set to 0.
-            eqir => bench[c] = if a == bench[b] { 1 } else { 0 },
-            // eqri (equal register/immediate) sets register C to 1 if register A is equal to value B. Otherwise, register C is set to 0.
-            eqri => bench[c] = if bench[a] == b { 1 } else { 0 },
-            // eqrr (equal register/register) sets register C to 1 if register A is equal to register B. Otherwise, register C is set to 0.
-            eqrr => bench[c] = if bench[a] == bench[b] { 1 } else { 0 },
-        }
-    }
-}
+use crate::elfcode::OpCode;
 
 #[derive(PartialEq, Eq, Debug)]
 struct Instr {
@@ -202,6 +131,16 @@ impl crate::Puzzle for Puzzle19 {
         cpu.run(&self.program);
         cpu.bench[0].to_string()
     }
+
+    fn query(&self, query: &str) -> Result<String, String> {
+        match query.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["assemble", path] => {
+                let source = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+                crate::elfcode::assemble(&source)
+            },
+            _ => Err(format!("unknown query {}, expected assemble <source file>", query))
+        }
+    }
 }
 
 #[cfg(test)]
@@ -251,6 +190,21 @@ seti 9 0 5"#;
         );
     }
 
+    #[test]
+    fn test_assembled() {
+        let source = r#"#ip 0
+    seti 5 0 r1
+    seti 6 0 r2
+    addi ip 1 ip    ; skips the next instruction
+    addr r1 r2 r3
+    setr r1 0 ip
+    seti 8 0 r4
+    seti 9 0 r5"#;
+        let assembled = crate::elfcode::assemble(source).unwrap();
+        assert_eq!(EXAMPLE, assembled);
+        assert_eq!(parse(EXAMPLE).1, parse(&assembled).1);
+    }
+
     #[test]
     fn test_example() {
         let (mut cpu, program) = parse(EXAMPLE);
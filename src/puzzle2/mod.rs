@@ -1,32 +1,176 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
 
 fn parse(input: String) -> Vec<String> {
     input.lines().map(|x| x.to_owned()).collect::<Vec<_>>()
 }
 
-#[derive(Debug)]
-struct Checksum {
-    twos: i32,
-    threes: i32
+// How many times each letter appears in a word.
+#[derive(PartialEq, Debug)]
+struct Profile(BTreeMap<char, usize>);
+
+impl Profile {
+    fn new(word: &str) -> Self {
+        Profile(word.chars().fold(BTreeMap::new(), |mut freqs, c| {
+            *freqs.entry(c).or_insert(0) += 1;
+            freqs
+        }))
+    }
+
+    // Whether some letter appears exactly that many times.
+    fn has(&self, multiplicity: usize) -> bool {
+        self.0.values().any(|freq| *freq == multiplicity)
+    }
+}
+
+// For each multiplicity, the number of words having some letter exactly that many times.
+#[derive(PartialEq, Debug)]
+struct Checksum(BTreeMap<usize, usize>);
+
+impl Checksum {
+    fn new<'a>(profiles: impl IntoIterator<Item = &'a Profile>, multiplicities: &[usize]) -> Self {
+        let init = multiplicities.iter().map(|m| (*m, 0)).collect::<BTreeMap<_, _>>();
+        Checksum(profiles.into_iter().fold(init, |mut counts, profile| {
+            counts.iter_mut().filter(|(m, _)| profile.has(**m)).for_each(|(_, count)| *count += 1);
+            counts
+        }))
+    }
+
+    fn value(&self) -> usize {
+        self.0.values().product()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Distance {
+    // the number of positions with different letters, only words of the same length are compared
+    Hamming,
+    // the number of insertions, deletions or substitutions
+    Levenshtein,
 }
 
-fn compute_checksum(s: &str) -> Checksum {
-    let char_freqs = s.chars().fold(HashMap::new(), |mut freqs, c| {
-        match freqs.get(&c) {
-            Some(freq) => {
-                let nfreq = *freq + 1;
-                freqs.insert(c, nfreq);
+impl FromStr for Distance {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hamming" => Ok(Distance::Hamming),
+            "levenshtein" => Ok(Distance::Levenshtein),
+            other => Err(format!("unknown distance {}", other)),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+struct Similar {
+    // indices of the two words, a < b
+    a: usize,
+    b: usize,
+    distance: usize,
+    // the letters the words have in common, in order
+    common: String,
+}
+
+fn hamming(a: &[char], b: &[char]) -> Option<(usize, String)> {
+    if a.len() != b.len() {
+        return None;
+    }
+    let common = a.iter().zip(b.iter()).filter(|(l, r)| l == r).map(|(l, _)| *l).collect::<String>();
+    Some((a.len() - common.chars().count(), common))
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> (usize, String) {
+    // d[i][j] is the distance between a[..i] and b[..j]
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    (0..=a.len()).for_each(|i| d[i][0] = i);
+    (0..=b.len()).for_each(|j| d[0][j] = j);
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = d[i - 1][j - 1] + if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = substitution.min(d[i - 1][j] + 1).min(d[i][j - 1] + 1);
+        }
+    }
+
+    // walk back along an optimal alignment to collect the matching letters
+    let (mut i, mut j) = (a.len(), b.len());
+    let mut common = Vec::new();
+    while i > 0 && j > 0 {
+        if a[i - 1] == b[j - 1] && d[i][j] == d[i - 1][j - 1] {
+            common.push(a[i - 1]);
+            i -= 1;
+            j -= 1;
+        } else if d[i][j] == d[i - 1][j - 1] + 1 {
+            i -= 1;
+            j -= 1;
+        } else if d[i][j] == d[i - 1][j] + 1 {
+            i -= 1;
+        } else {
+            j -= 1;
+        }
+    }
+    (d[a.len()][b.len()], common.iter().rev().collect())
+}
+
+// Keys shared by any two words within distance k of each other.
+//   Hamming: split into k + 1 chunks, at least one of them is left untouched by k substitutions.
+//   Levenshtein: the deletion neighbourhood, both words reduce to the same string by deleting at most k letters each.
+fn keys(word: &[char], k: usize, distance: Distance) -> HashSet<String> {
+    match distance {
+        Distance::Hamming => (0..=k)
+            .map(|chunk| {
+                let (from, to) = (chunk * word.len() / (k + 1), (chunk + 1) * word.len() / (k + 1));
+                format!("{}:{}:{}", word.len(), chunk, word[from..to].iter().collect::<String>())
+            })
+            .collect(),
+        Distance::Levenshtein => {
+            let mut keys = HashSet::new();
+            let mut current = HashSet::new();
+            current.insert(word.iter().collect::<String>());
+            for _ in 0..k {
+                let next = current
+                    .iter()
+                    .flat_map(|w: &String| {
+                        let chars = w.chars().collect::<Vec<_>>();
+                        (0..chars.len()).map(move |i| chars[..i].iter().chain(chars[i + 1..].iter()).collect::<String>())
+                    })
+                    .collect::<HashSet<_>>();
+                keys.extend(current);
+                current = next;
             }
-            None => { freqs.insert(c, 1); }
-        };
-        freqs
-    });
-
-    char_freqs.iter().fold(Checksum { twos: 0, threes: 0 }, |mut check, (_key, value)| {
-        if *value == 2 { check.twos = 1; }
-        if *value == 3 { check.threes = 1; }
-        check
-    })
+            keys.extend(current);
+            keys
+        }
+    }
+}
+
+// Finds all pairs of words within distance k of each other.
+//   Words are indexed by keys that similar words necessarily share, so only words sharing a key are compared.
+fn similar(words: &[String], k: usize, distance: Distance) -> Vec<Similar> {
+    let chars = words.iter().map(|w| w.chars().collect::<Vec<_>>()).collect::<Vec<_>>();
+    let mut index: HashMap<String, Vec<usize>> = HashMap::new();
+    for (idx, word) in chars.iter().enumerate() {
+        for key in keys(word, k, distance) {
+            index.entry(key).or_default().push(idx);
+        }
+    }
+
+    let candidates = index
+        .values()
+        .flat_map(|words| words.iter().enumerate().flat_map(move |(i, a)| words[i + 1..].iter().map(move |b| (*a, *b))))
+        .collect::<HashSet<_>>();
+
+    let mut found = candidates
+        .into_iter()
+        .filter_map(|(a, b)| {
+            let compared = match distance {
+                Distance::Hamming => hamming(&chars[a], &chars[b]),
+                Distance::Levenshtein => Some(levenshtein(&chars[a], &chars[b])),
+            };
+            compared.filter(|(d, _)| *d <= k).map(|(distance, common)| Similar { a, b, distance, common })
+        })
+        .collect::<Vec<_>>();
+    found.sort_by_key(|s| (s.a, s.b));
+    found
 }
 
 pub fn mk(input: String) -> Box<dyn crate::Puzzle> {
@@ -40,32 +184,107 @@ pub struct Puzzle2 {
 impl crate::Puzzle for Puzzle2 {
 
     fn part1(&self) -> String {
-        let checksum: Checksum = self.words
-            .iter()
-            .map(|word| compute_checksum(word))
-            .fold(Checksum { twos: 0, threes: 0 }, |acc, c| {
-                Checksum {
-                    twos: acc.twos + c.twos,
-                    threes: acc.threes + c.threes
-                }
-            });
-
-        (checksum.twos * checksum.threes).to_string()
+        let profiles = self.words.iter().map(|word| Profile::new(word)).collect::<Vec<_>>();
+        Checksum::new(&profiles, &[2, 3]).value().to_string()
     }
     
     fn part2(&self) -> String {
+        similar(&self.words, 1, Distance::Hamming)
+            .into_iter()
+            .find(|s| s.distance == 1)
+            .expect("couldn't find a box")
+            .common
+    }
 
-        let found = self.words.iter()
-            .flat_map(|a| self.words.iter().map(move |b| (a,b)))
-            .find_map(|(a,b)| {
-                let common = a.chars().zip(b.chars())
-                    .filter_map(|(l,r)| if l == r { Some(l) } else { None } )
-                    .collect::<String>();
+    // e.g.: `checksum 2 3 4`, `profile` or `similar hamming 2`
+    fn query(&self, query: &str) -> Result<String, String> {
+        match query.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["checksum", multiplicities @ ..] => {
+                let multiplicities = multiplicities
+                    .iter()
+                    .map(|m| usize::from_str(m).map_err(|_| format!("invalid multiplicity {}", m)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let profiles = self.words.iter().map(|word| Profile::new(word)).collect::<Vec<_>>();
+                let checksum = Checksum::new(&profiles, &multiplicities);
+                let counts = checksum.0.iter().map(|(m, count)| format!("{} words have a letter {} times", count, m)).collect::<Vec<_>>();
+                Ok(format!("{}\nchecksum: {}", counts.join("\n"), checksum.value()))
+            }
+            ["profile"] => Ok(self
+                .words
+                .iter()
+                .map(|word| {
+                    let profile = Profile::new(word);
+                    let freqs = profile.0.iter().map(|(c, freq)| format!("{}{}", c, freq)).collect::<Vec<_>>();
+                    format!("{}: {}", word, freqs.join(" "))
+                })
+                .collect::<Vec<_>>()
+                .join("\n")),
+            ["similar", distance, k] => {
+                let distance = Distance::from_str(distance)?;
+                let k = usize::from_str(k).map_err(|_| format!("invalid distance {}", k))?;
+                Ok(similar(&self.words, k, distance)
+                    .iter()
+                    .map(|s| format!("{} {} (distance {}): {}", self.words[s.a], self.words[s.b], s.distance, s.common))
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            _ => Err(format!("unknown query {}, expected one of: checksum <multiplicities..>, profile, similar <hamming|levenshtein> <k>", query)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn words(s: &str) -> Vec<String> {
+        s.split_whitespace().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_checksum() {
+        let profiles = words("abcdef bababc abbcde abcccd aabcdd abcdee ababab").iter().map(|w| Profile::new(w)).collect::<Vec<_>>();
+        assert_eq!(Profile(vec![('a', 2), ('b', 3), ('c', 1)].into_iter().collect()), profiles[1]);
+
+        let checksum = Checksum::new(&profiles, &[2, 3]);
+        assert_eq!(Checksum(vec![(2, 4), (3, 3)].into_iter().collect()), checksum);
+        assert_eq!(12, checksum.value());
 
-                if common.len() != a.len() -1 { None }
-                else { Some(common) }
-            });
+        let checksum = Checksum::new(&profiles, &[1, 2, 3, 4]);
+        assert_eq!(vec![6, 4, 3, 0], checksum.0.values().cloned().collect::<Vec<_>>());
+        assert_eq!(0, checksum.value());
+    }
+
+    #[test]
+    fn test_example() {
+        let words = words("abcde fghij klmno pqrst fguij axcye wvxyz");
+        assert_eq!(vec![Similar { a: 1, b: 4, distance: 1, common: "fgij".to_string() }], similar(&words, 1, Distance::Hamming));
+        assert_eq!(
+            vec![(0, 5, 2), (1, 4, 1)],
+            similar(&words, 2, Distance::Hamming).iter().map(|s| (s.a, s.b, s.distance)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        let words = words("abcd abd xabcd abcd wxyz");
+        assert_eq!(
+            vec![(0, 1, 1, "abd"), (0, 2, 1, "abcd"), (0, 3, 0, "abcd"), (1, 3, 1, "abd"), (2, 3, 1, "abcd")],
+            similar(&words, 1, Distance::Levenshtein).iter().map(|s| (s.a, s.b, s.distance, s.common.as_str())).collect::<Vec<_>>()
+        );
+        assert_eq!((3, "ittn".to_string()), levenshtein(&"kitten".chars().collect::<Vec<_>>(), &"sitting".chars().collect::<Vec<_>>()));
+    }
 
-        found.expect("couldn't find a box")
+    #[test]
+    fn test_matches_brute_force() {
+        let words = parse(include_str!("input.txt").to_string());
+        let chars = words.iter().map(|w| w.chars().collect::<Vec<_>>()).collect::<Vec<_>>();
+        for k in 0..=3 {
+            let expected = (0..words.len())
+                .flat_map(|a| (a + 1..words.len()).map(move |b| (a, b)))
+                .filter(|(a, b)| hamming(&chars[*a], &chars[*b]).unwrap().0 <= k)
+                .collect::<Vec<_>>();
+            assert_eq!(expected, similar(&words, k, Distance::Hamming).iter().map(|s| (s.a, s.b)).collect::<Vec<_>>());
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,125 @@
+use super::compile::Compiled;
+use super::{Cpu, Instr, OpCode, State};
+use std::collections::HashSet;
+
+// The only instruction reading register 0, comparing it against another register.
+//   Programs that halt depending on register 0 do so right after this comparison succeeds.
+#[derive(PartialEq, Debug)]
+pub(super) struct Comparison {
+    pub(super) address: usize,
+    pub(super) register: usize,
+}
+
+pub(super) fn r0_comparison(program: &[Instr]) -> Result<Comparison, String> {
+    let mut comparisons = Vec::new();
+    for (address, i) in program.iter().enumerate() {
+        if i.c == 0 {
+            return Err(format!("register 0 is written to at ip={}", address));
+        }
+        let (a_is_register, b_is_register) = i.code.register_inputs();
+        if !((a_is_register && i.a == 0) || (b_is_register && i.b == 0)) {
+            continue;
+        }
+        match (&i.code, i.a, i.b) {
+            (OpCode::eqrr, 0, other) | (OpCode::eqrr, other, 0) if other != 0 => comparisons.push(Comparison { address, register: other }),
+            _ => return Err(format!("register 0 is used by something other than a comparison at ip={}: {:?}", address, i)),
+        }
+    }
+
+    match comparisons.len() {
+        1 => Ok(comparisons.pop().unwrap()),
+        0 => Err("register 0 is never compared against".to_string()),
+        n => Err(format!("register 0 is compared against in {} places", n)),
+    }
+}
+
+// The values compared against register 0, in order of first appearance, until the program loops.
+//   Setting register 0 to the first value halts the program after the fewest instructions, the last one after the most.
+pub(super) struct ComparedValues {
+    compiled: Compiled,
+    register: usize,
+    cpu: Cpu,
+    states: HashSet<[usize; 6]>,
+    values: HashSet<usize>,
+}
+
+impl ComparedValues {
+    pub(super) fn new(cpu: &Cpu, program: &[Instr]) -> Result<Self, String> {
+        let comparison = r0_comparison(program)?;
+        // the comparison is the only use of register 0 so its value doesn't matter, unless it happens to match
+        let mut cpu = cpu.clone();
+        cpu.bench[0] = usize::MAX;
+        Ok(ComparedValues {
+            compiled: Compiled::new(cpu.ip_register, program, Some(comparison.address)),
+            register: comparison.register,
+            cpu,
+            states: HashSet::new(),
+            values: HashSet::new(),
+        })
+    }
+}
+
+impl Iterator for ComparedValues {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        loop {
+            match self.compiled.run(&mut self.cpu) {
+                State::Halt => break None,
+                State::Breakpoint => {
+                    // the whole bench is the program's state when reaching the comparison, it loops once it's been seen before
+                    if !self.states.insert(self.cpu.bench) {
+                        break None;
+                    }
+                    let value = self.cpu.bench[self.register];
+                    if self.values.insert(value) {
+                        break Some(value);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::elfcode::assemble;
+    use super::super::parse;
+
+    #[test]
+    fn test_r0_comparison() {
+        let (_, program) = parse(include_str!("input.txt"));
+        assert_eq!(Ok(Comparison { address: 28, register: 3 }), r0_comparison(&program));
+
+        let (_, program) = parse("#ip 5\naddi 0 1 1");
+        assert_eq!(Err("register 0 is used by something other than a comparison at ip=0: Instr { code: addi, a: 0, b: 1, c: 1 }".to_string()), r0_comparison(&program));
+    }
+
+    #[test]
+    fn test_compared_values() {
+        // compares r0 against 3, 6, 9, 2, 5, 8, 1, 4, 7, 0, 3, ... and halts when equal
+        let source = r#"#ip 5
+#reg value 1
+#reg flag 4
+            seti 0 0 value
+loop:       addi value 3 value      ; value = (value + 3) % 10
+            gtri value 9 flag
+            addr flag ip ip
+            goto compare
+            addi value 6 value      ; + 6 wraps at 16, so the value is back under 10
+            bani value 15 value
+compare:    eqrr value r0 flag
+            addr flag ip ip
+            goto loop
+"#;
+        let (cpu, program) = parse(&assemble(source).unwrap());
+        let values = ComparedValues::new(&cpu, &program).unwrap().collect::<Vec<_>>();
+        assert_eq!(vec![3, 6, 9, 2, 5, 8, 1, 4, 7, 0], values);
+
+        let compiled = Compiled::new(cpu.ip_register, &program, None);
+        let mut halting = cpu.clone();
+        halting.bench[0] = 7;
+        assert_eq!(State::Halt, compiled.run(&mut halting));
+    }
+}
@@ -0,0 +1,140 @@
+use super::{Cpu, Instr, OpCode, State};
+use std::collections::HashSet;
+
+type Op = Box<dyn Fn(&mut [usize; 6])>;
+type Jump = Box<dyn Fn(&mut [usize; 6]) -> usize>;
+
+enum Exit {
+    // the next instruction is known when compiling: fall through, `seti X _ ip`, `addi ip X ip`, ...
+    Static(usize),
+    // the next instruction depends on a register, e.g.: `addr 4 ip ip`
+    Dynamic(Jump),
+}
+
+// A run of instructions starting at some address, following static jumps until the next one depends on a register.
+struct Block {
+    ops: Vec<Op>,
+    exit: Exit,
+}
+
+// Pre-decodes the program into closures specialised on each instruction's opcode and operands.
+//   There's one block per address (dynamic jumps can land anywhere) and statically known jumps are threaded through,
+//   so running the program doesn't need to match on OpCodes nor update the instruction pointer register on every step.
+pub(super) struct Compiled {
+    ip_register: usize,
+    breakpoint: Option<usize>,
+    blocks: Vec<Block>,
+}
+
+impl Compiled {
+    pub(super) fn new(ip_register: usize, program: &[Instr], breakpoint: Option<usize>) -> Self {
+        let blocks = (0..program.len())
+            .map(|start| {
+                let mut ops = Vec::new();
+                let mut visited = HashSet::new();
+                let mut ip = start;
+                let exit = loop {
+                    // stop at the end of the program, when looping back into this block or before the breakpoint
+                    //   (the Debugger breaks once the ip reaches it, so the first instruction of the block doesn't count)
+                    if ip >= program.len() || !visited.insert(ip) || (ip != start && Some(ip) == breakpoint) {
+                        break Exit::Static(ip);
+                    }
+                    let instr = &program[ip];
+                    if instr.c != ip_register {
+                        ops.push(compile(instr, ip_register, ip));
+                        ip += 1;
+                    } else if is_static(instr, ip_register) {
+                        let mut bench = [0; 6];
+                        bench[ip_register] = ip;
+                        instr.code.run(&mut bench, instr.a, instr.b, instr.c);
+                        ip = bench[ip_register] + 1;
+                    } else {
+                        let op = compile(instr, ip_register, ip);
+                        break Exit::Dynamic(Box::new(move |r| {
+                            op(r);
+                            r[ip_register] + 1
+                        }));
+                    }
+                };
+                Block { ops, exit }
+            })
+            .collect();
+
+        Compiled { ip_register, breakpoint, blocks }
+    }
+
+    // Same semantics as the Debugger stepping through the program with Cpu::step.
+    pub(super) fn run(&self, cpu: &mut Cpu) -> State {
+        assert_eq!(self.ip_register, cpu.ip_register, "program was compiled for another ip register");
+        if cpu.ip >= self.blocks.len() {
+            return State::Halt;
+        }
+
+        let bench = &mut cpu.bench;
+        let mut ip = cpu.ip;
+        let state = loop {
+            match self.blocks.get(ip) {
+                None => break State::Halt,
+                Some(block) => {
+                    block.ops.iter().for_each(|op| op(bench));
+                    ip = match &block.exit {
+                        Exit::Static(next) => *next,
+                        Exit::Dynamic(jump) => jump(bench),
+                    };
+                    if Some(ip) == self.breakpoint {
+                        break State::Breakpoint;
+                    }
+                }
+            }
+        };
+        // Cpu::step leaves the register one behind the instruction pointer
+        cpu.ip = ip;
+        bench[self.ip_register] = ip - 1;
+        state
+    }
+}
+
+// Whether the instruction only reads the ip register, in which case its result is known when compiling.
+fn is_static(i: &Instr, ip_register: usize) -> bool {
+    let (a_is_register, b_is_register) = i.code.register_inputs();
+    (!a_is_register || i.a == ip_register) && (!b_is_register || i.b == ip_register)
+}
+
+fn compile(i: &Instr, ip_register: usize, ip: usize) -> Op {
+    use OpCode::*;
+    let (a, b, c) = (i.a, i.b, i.c);
+    let op: Op = match i.code {
+        addr => Box::new(move |r| r[c] = r[a] + r[b]),
+        addi => Box::new(move |r| r[c] = r[a] + b),
+
+        mulr => Box::new(move |r| r[c] = r[a] * r[b]),
+        muli => Box::new(move |r| r[c] = r[a] * b),
+
+        banr => Box::new(move |r| r[c] = r[a] & r[b]),
+        bani => Box::new(move |r| r[c] = r[a] & b),
+
+        borr => Box::new(move |r| r[c] = r[a] | r[b]),
+        bori => Box::new(move |r| r[c] = r[a] | b),
+
+        setr => Box::new(move |r| r[c] = r[a]),
+        seti => Box::new(move |r| r[c] = a),
+
+        gtir => Box::new(move |r| r[c] = (a > r[b]) as usize),
+        gtri => Box::new(move |r| r[c] = (r[a] > b) as usize),
+        gtrr => Box::new(move |r| r[c] = (r[a] > r[b]) as usize),
+
+        eqir => Box::new(move |r| r[c] = (a == r[b]) as usize),
+        eqri => Box::new(move |r| r[c] = (r[a] == b) as usize),
+        eqrr => Box::new(move |r| r[c] = (r[a] == r[b]) as usize),
+    };
+
+    // the ip register is only kept up to date for the instructions that read it
+    if a == ip_register || b == ip_register || c == ip_register {
+        Box::new(move |r| {
+            r[ip_register] = ip;
+            op(r)
+        })
+    } else {
+        op
+    }
+}
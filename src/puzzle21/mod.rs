@@ -1,80 +1,13 @@
 use regex::Regex;
-use serde::Deserialize;
-use std::collections::HashSet;
+use std::fs;
 use std::str::FromStr;
 
-#[allow(non_camel_case_types)]
-#[derive(PartialEq, Eq, Hash, Deserialize, Clone, Debug)]
-enum OpCode {
-    addr,
-    addi,
+mod analysis;
+mod compile;
+mod symbolic;
 
-    mulr,
-    muli,
-
-    banr,
-    bani,
-
-    borr,
-    bori,
-
-    setr,
-    seti,
-
-    gtir,
-    gtri,
-    gtrr,
-
-    eqir,
-    eqri,
-    eqrr,
-}
-
-impl OpCode {
-    fn run(&self, bench: &mut [usize; 6], a: usize, b: usize, c: usize) {
-        use OpCode::*;
-        match self {
-            // addr (add register) stores into register C the result of adding register A and register B.
-            addr => bench[c] = bench[a] + bench[b],
-            // addi (add immediate) stores into register C the result of adding register A and value B.
-            addi => bench[c] = bench[a] + b,
-
-            // mulr (multiply register) stores into register C the result of multiplying register A and register B.
-            mulr => bench[c] = bench[a] * bench[b],
-            // muli (multiply immediate) stores into register C the result of multiplying register A and value B.
-            muli => bench[c] = bench[a] * b,
-
-            // banr (bitwise AND register) stores into register C the result of the bitwise AND of register A and register B.
-            banr => bench[c] = bench[a] & bench[b],
-            // bani (bitwise AND immediate) stores into register C the result of the bitwise AND of register A and value B.
-            bani => bench[c] = bench[a] & b,
-
-            // borr (bitwise OR register) stores into register C the result of the bitwise OR of register A and register B.
-            borr => bench[c] = bench[a] | bench[b],
-            // bori (bitwise OR immediate) stores into register C the result of the bitwise OR of register A and value B.
-            bori => bench[c] = bench[a] | b,
-
-            // setr (set register) copies the contents of register A into register C. (Input B is ignored.)
-            setr => bench[c] = bench[a],
-            // seti (set immediate) stores value A into register C. (Input B is ignored.)
-            seti => bench[c] = a,
-
-            // gtir (greater-than immediate/register) sets register C to 1 if value A is greater than register B. Otherwise, register C is set to 0.
-            gtir => bench[c] = if a > bench[b] { 1 } else { 0 },
-            // gtri (greater-than register/immediate) sets register C to 1 if register A is greater than value B. Otherwise, register C is set to 0.
-            gtri => bench[c] = if bench[a] > b { 1 } else { 0 },
-            // gtrr (greater-than register/register) sets register C to 1 if register A is greater than register B. Otherwise, register C is set to 0.
-            gtrr => bench[c] = if bench[a] > bench[b] { 1 } else { 0 },
-
-            // eqir (equal immediate/register) sets register C to 1 if value A is equal to register B. Otherwise, register C is set to 0.
-            eqir => bench[c] = if a == bench[b] { 1 } else { 0 },
-            // eqri (equal register/immediate) sets register C to 1 if register A is equal to value B. Otherwise, register C is set to 0.
-            eqri => bench[c] = if bench[a] == b { 1 } else { 0 },
-            // eqrr (equal register/register) sets register C to 1 if register A is equal to register B. Otherwise, register C is set to 0.
-            eqrr => bench[c] = if bench[a] == bench[b] { 1 } else { 0 },
-        }
-    }
-}
+use crate::elfcode::OpCode;
+use analysis::{r0_comparison, ComparedValues};
 
 #[derive(PartialEq, Eq, Clone, Debug)]
 struct Instr {
@@ -126,6 +59,7 @@ impl Cpu {
     }
 }
 
+#[derive(PartialEq, Debug)]
 enum State {
     Breakpoint,
     Halt,
@@ -139,20 +73,29 @@ struct Debugger {
 
 impl Debugger {
     fn run(&mut self) -> State {
-        loop {
+        self.run_for(u64::MAX).expect("the program ran for u64::MAX steps")
+    }
+
+    // Runs at most `steps` instructions, None when the program is still running after them.
+    fn run_for(&mut self, steps: u64) -> Option<State> {
+        for _ in 0..steps {
             match self.program.get(self.cpu.ip) {
-                None => break State::Halt,
+                None => return Some(State::Halt),
                 Some(instr) => {
                     self.cpu.step(instr);
                     if self.cpu.ip == self.breakpoint {
-                        break State::Breakpoint;
+                        return Some(State::Breakpoint);
                     }
                 }
             };
         }
+        None
     }
 }
 
+// How many instructions assembled programs may run for, as hand-written ones can easily loop forever.
+const RUN_STEPS: u64 = 100_000_000;
+
 fn parse(input: &str) -> (Cpu, Vec<Instr>) {
     let ip_register = match input.lines().take(1).last() {
         None => panic!("empty input"),
@@ -312,32 +255,168 @@ do {
 */
 impl crate::Puzzle for Puzzle21 {
     fn part1(&self) -> String {
-        // ip=28 is when we compare against register 0 with R3, so we simply need to stop at that point and check what the contents of R3 is
+        // setting register 0 to whatever it's first compared against halts the program after the fewest instructions,
+        //   so we simply need to stop at that comparison and check the contents of the other register
+        let comparison = r0_comparison(&self.program).expect("cannot analyze program");
         let mut debug = Debugger {
             cpu: self.cpu.clone(),
             program: self.program.clone(),
-            breakpoint: 28,
+            breakpoint: comparison.address,
         };
         debug.run();
-        debug.cpu.bench[3].to_string()
+        debug.cpu.bench[comparison.register].to_string()
     }
 
     fn part2(&self) -> String {
-        // the most instructions is right before R3 loops around to some value we've seen before.
-        let mut debug = Debugger {
-            cpu: self.cpu.clone(),
-            program: self.program.clone(),
-            breakpoint: 28,
-        };
-        let mut seen = HashSet::new();
-        let mut prev = 0 as usize;
-        let found = loop {
-            debug.run();
-            if !seen.insert(debug.cpu.bench[3]) {
-                break prev;
+        // the most instructions is the last new value compared against register 0 before the program loops around.
+        //   this takes billions of steps, so ComparedValues runs the compiled program rather than the Debugger.
+        ComparedValues::new(&self.cpu, &self.program)
+            .expect("cannot analyze program")
+            .last()
+            .expect("register 0 is never compared against")
+            .to_string()
+    }
+
+    fn query(&self, query: &str) -> Result<String, String> {
+        match query {
+            "blocks" => {
+                let blocks = symbolic::blocks(self.cpu.ip_register, &self.program);
+                return Ok(blocks.iter().map(|b| b.to_string()).collect::<Vec<_>>().join("\n"));
+            }
+            "paths" => {
+                let paths = symbolic::explore(self.cpu.ip_register, &self.program, symbolic::unknown_r0(), 5_000_000, 10);
+                let describe = |p: &symbolic::Path| match p.r0() {
+                    None => p.to_string(),
+                    Some(r0) => format!("{}\n  => r0 = {}", p, r0),
+                };
+                return Ok(paths.iter().map(describe).collect::<Vec<_>>().join("\n"));
             }
-            prev = debug.cpu.bench[3];
+            _ => (),
+        }
+        let assemble = |path: &str| {
+            let source = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+            crate::elfcode::assemble(&source)
+        };
+        match query.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["assemble", path] => return assemble(path),
+            ["run", path] => return run_assembled(&assemble(path)?, RUN_STEPS),
+            _ => (),
+        }
+
+        let mut values = ComparedValues::new(&self.cpu, &self.program)?;
+        let found = match query {
+            "fewest" => values.next(),
+            "most" => values.last(),
+            "compared" => return Ok(values.map(|v| v.to_string()).collect::<Vec<_>>().join("\n")),
+            _ => return Err(format!("unknown query {}, expected one of: fewest, most, compared, blocks, paths, assemble <source file>, run <source file>", query)),
         };
-        found.to_string()
+        found.map(|v| v.to_string()).ok_or_else(|| "no value of register 0 halts the program".to_string())
+    }
+}
+
+// Runs an assembled program from all registers at 0 until it halts, or fails once it has run for the given steps.
+fn run_assembled(assembled: &str, steps: u64) -> Result<String, String> {
+    let (cpu, program) = parse(assembled);
+    let mut debugger = Debugger { cpu, program, breakpoint: usize::MAX };
+    match debugger.run_for(steps) {
+        Some(_) => Ok(format!("halted with registers {:?}", debugger.cpu.bench)),
+        None => Err(format!("still running after {} steps, with registers {:?}", steps, debugger.cpu.bench)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use compile::Compiled;
+    use std::time::Instant;
+
+    // Stops on the R3 == R0 comparison for each of the first n values of R3
+    fn r3_values(mut run: impl FnMut() -> (State, usize), n: usize) -> Vec<usize> {
+        (0..n).map(|_| {
+            let (state, r3) = run();
+            assert_eq!(State::Breakpoint, state);
+            r3
+        }).collect()
+    }
+
+    #[test]
+    fn test_compiled_matches_step() {
+        let (cpu, program) = parse(include_str!("input.txt"));
+        let mut debug = Debugger { cpu: cpu.clone(), program: program.clone(), breakpoint: 28 };
+        let expected = r3_values(|| (debug.run(), debug.cpu.bench[3]), 10);
+
+        let compiled = Compiled::new(cpu.ip_register, &program, Some(28));
+        let mut compiled_cpu = cpu.clone();
+        let actual = r3_values(|| (compiled.run(&mut compiled_cpu), compiled_cpu.bench[3]), 10);
+
+        assert_eq!(expected, actual);
+        assert_eq!(debug.cpu.ip, compiled_cpu.ip);
+        assert_eq!(debug.cpu.bench, compiled_cpu.bench);
+    }
+
+    #[test]
+    fn test_compiled_halt() {
+        let (mut cpu, program) = parse(include_str!("../puzzle19/input.txt"));
+        let mut debug = Debugger { cpu: cpu.clone(), program: program.clone(), breakpoint: program.len() };
+        debug.run();
+
+        assert_eq!(State::Halt, Compiled::new(cpu.ip_register, &program, None).run(&mut cpu));
+        assert_eq!(debug.cpu.ip, cpu.ip);
+        assert_eq!(debug.cpu.bench, cpu.bench);
+    }
+
+    // cargo test --release bench_compiled -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_compiled() {
+        // day 19, part1 runs to completion
+        let (cpu, program) = parse(include_str!("../puzzle19/input.txt"));
+        let mut debug = Debugger { cpu: cpu.clone(), program: program.clone(), breakpoint: program.len() };
+        let start = Instant::now();
+        debug.run();
+        println!("day 19 Cpu::step: {:?}", start.elapsed());
+
+        let compiled = Compiled::new(cpu.ip_register, &program, None);
+        let mut compiled_cpu = cpu.clone();
+        let start = Instant::now();
+        compiled.run(&mut compiled_cpu);
+        println!("day 19 compiled:  {:?}", start.elapsed());
+        assert_eq!(debug.cpu.bench, compiled_cpu.bench);
+
+        // day 21, the first thousand comparisons against R0
+        let (cpu, program) = parse(include_str!("input.txt"));
+        let mut debug = Debugger { cpu: cpu.clone(), program: program.clone(), breakpoint: 28 };
+        let start = Instant::now();
+        let expected = r3_values(|| (debug.run(), debug.cpu.bench[3]), 1000);
+        println!("day 21 Cpu::step: {:?}", start.elapsed());
+
+        let compiled = Compiled::new(cpu.ip_register, &program, Some(28));
+        let mut compiled_cpu = cpu.clone();
+        let start = Instant::now();
+        let actual = r3_values(|| (compiled.run(&mut compiled_cpu), compiled_cpu.bench[3]), 1000);
+        println!("day 21 compiled:  {:?}", start.elapsed());
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_run_assembled() {
+        let source = r#"
+; counts r0 up to 10
+#ip 5
+#reg counter 0
+    seti 0 0 r1     ; padding so that the loop doesn't start at 0
+loop:
+    addi counter 1 counter
+    gtri counter 9 r4
+    addr r4 ip ip   ; skips the goto once counter > 9
+    goto loop
+done: seti 99 0 r3
+"#;
+        let assembled = crate::elfcode::assemble(source).unwrap();
+        assert_eq!(Ok("halted with registers [10, 0, 0, 99, 1, 5]".to_string()), run_assembled(&assembled, 1000));
+        assert_eq!(Err("still running after 10 steps, with registers [3, 0, 0, 0, 0, 1]".to_string()), run_assembled(&assembled, 10));
+
+        let forever = crate::elfcode::assemble("#ip 5\nseti 0 0 r1\nloop: addi r0 1 r0\ngoto loop").unwrap();
+        assert_eq!(Err("still running after 1000 steps, with registers [500, 0, 0, 0, 0, 1]".to_string()), run_assembled(&forever, 1000));
     }
 }
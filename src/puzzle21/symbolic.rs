@@ -0,0 +1,364 @@
+use super::{Instr, OpCode};
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub(super) enum BinOp {
+    Add,
+    Mul,
+    And,
+    Or,
+    Gt,
+    Eq,
+}
+
+// The value of a register as an expression over the registers' values at some starting point.
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub(super) enum Expr {
+    Const(usize),
+    Reg(usize),
+    Bin(BinOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    // Builds the expression, folding constants and trivial identities along the way.
+    fn bin(op: BinOp, a: Expr, b: Expr) -> Expr {
+        use BinOp::*;
+        use Expr::*;
+        match (op, &a, &b) {
+            (Add, Const(x), Const(y)) if x.checked_add(*y).is_some() => Const(x + y),
+            (Mul, Const(x), Const(y)) if x.checked_mul(*y).is_some() => Const(x * y),
+            (And, Const(x), Const(y)) => Const(x & y),
+            (Or, Const(x), Const(y)) => Const(x | y),
+            (Gt, Const(x), Const(y)) => Const((x > y) as usize),
+            (Eq, Const(x), Const(y)) => Const((x == y) as usize),
+
+            (Add, Const(0), _) | (Mul, Const(1), _) | (Or, Const(0), _) => b,
+            (Add, _, Const(0)) | (Mul, _, Const(1)) | (Or, _, Const(0)) => a,
+            (Mul, Const(0), _) | (Mul, _, Const(0)) | (And, Const(0), _) | (And, _, Const(0)) => Const(0),
+            (Gt, _, _) if a == b => Const(0),
+            (Eq, _, _) if a == b => Const(1),
+            _ => Bin(op, Box::new(a), Box::new(b)),
+        }
+    }
+
+    // Comparisons are the only expressions known to be either 0 or 1.
+    fn is_boolean(&self) -> bool {
+        match self {
+            Expr::Const(v) => *v <= 1,
+            Expr::Reg(_) => false,
+            Expr::Bin(op, _, _) => *op == BinOp::Gt || *op == BinOp::Eq,
+        }
+    }
+
+    // The distinct comparisons within the expression.
+    fn comparisons(&self, found: &mut Vec<Expr>) {
+        if let Expr::Bin(_, a, b) = self {
+            if self.is_boolean() {
+                if !found.contains(self) {
+                    found.push(self.clone());
+                }
+            } else {
+                a.comparisons(found);
+                b.comparisons(found);
+            }
+        }
+    }
+
+    fn substitute(&self, from: &Expr, to: &Expr) -> Expr {
+        if self == from {
+            to.clone()
+        } else {
+            match self {
+                Expr::Bin(op, a, b) => Expr::bin(*op, a.substitute(from, to), b.substitute(from, to)),
+                other => other.clone(),
+            }
+        }
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::Const(v) => write!(f, "{}", v),
+            Expr::Reg(r) => write!(f, "r{}", r),
+            Expr::Bin(op, a, b) => {
+                let symbol = match op {
+                    BinOp::Add => "+",
+                    BinOp::Mul => "*",
+                    BinOp::And => "&",
+                    BinOp::Or => "|",
+                    BinOp::Gt => ">",
+                    BinOp::Eq => "==",
+                };
+                write!(f, "({} {} {})", a, symbol, b)
+            }
+        }
+    }
+}
+
+type Registers = [Expr; 6];
+
+fn initial_registers() -> Registers {
+    [Expr::Reg(0), Expr::Reg(1), Expr::Reg(2), Expr::Reg(3), Expr::Reg(4), Expr::Reg(5)]
+}
+
+fn step(regs: &mut Registers, i: &Instr, ip_register: usize, ip: usize) {
+    use BinOp::*;
+    use OpCode::*;
+    regs[ip_register] = Expr::Const(ip);
+    let (a_is_register, b_is_register) = i.code.register_inputs();
+    let a = if a_is_register { regs[i.a].clone() } else { Expr::Const(i.a) };
+    let b = if b_is_register { regs[i.b].clone() } else { Expr::Const(i.b) };
+    regs[i.c] = match i.code {
+        addr | addi => Expr::bin(Add, a, b),
+        mulr | muli => Expr::bin(Mul, a, b),
+        banr | bani => Expr::bin(And, a, b),
+        borr | bori => Expr::bin(Or, a, b),
+        setr | seti => a,
+        gtir | gtri | gtrr => Expr::bin(Gt, a, b),
+        eqir | eqri | eqrr => Expr::bin(Eq, a, b),
+    };
+}
+
+// Where the program goes after an instruction: either a single address,
+//   or one of two addresses depending on whether a comparison holds (false first).
+enum Next {
+    Address(usize),
+    Branch(Expr, usize, usize),
+    Unknown(Expr),
+}
+
+fn next(regs: &Registers, ip_register: usize) -> Next {
+    let ip = Expr::bin(BinOp::Add, regs[ip_register].clone(), Expr::Const(1));
+    if let Expr::Const(address) = ip {
+        return Next::Address(address);
+    }
+    let mut comparisons = Vec::new();
+    ip.comparisons(&mut comparisons);
+    if comparisons.len() == 1 {
+        let condition = comparisons.pop().unwrap();
+        let when = |value| ip.substitute(&condition, &Expr::Const(value));
+        if let (Expr::Const(f), Expr::Const(t)) = (when(0), when(1)) {
+            return Next::Branch(condition, f, t);
+        }
+    }
+    Next::Unknown(ip)
+}
+
+// A straight-line run of instructions summarised as formulas over the registers' values when entering it.
+pub(super) struct Block {
+    start: usize,
+    end: usize,
+    transfers: Vec<(usize, Expr)>,
+    exit: Next,
+}
+
+impl Display for Block {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "ip={}..={}", self.start, self.end)?;
+        for (r, expr) in &self.transfers {
+            writeln!(f, "  r{} := {}", r, expr)?;
+        }
+        match &self.exit {
+            Next::Address(address) => write!(f, "  goto {}", address),
+            Next::Branch(condition, f_address, t_address) => write!(f, "  if {} goto {} else goto {}", condition, t_address, f_address),
+            Next::Unknown(ip) => write!(f, "  goto {}", ip),
+        }
+    }
+}
+
+// Splits the program where instructions jump (or are jumped to) and summarises each block.
+pub(super) fn blocks(ip_register: usize, program: &[Instr]) -> Vec<Block> {
+    let mut leaders = BTreeSet::new();
+    leaders.insert(0);
+    loop {
+        let blocks = leaders.iter().filter(|start| **start < program.len()).map(|start| block(ip_register, program, *start, &leaders)).collect::<Vec<_>>();
+        let targets = blocks
+            .iter()
+            .flat_map(|b| match &b.exit {
+                Next::Address(address) => vec![*address],
+                Next::Branch(_, f, t) => vec![*f, *t],
+                Next::Unknown(_) => vec![b.end + 1],
+            })
+            .collect::<Vec<_>>();
+        let before = leaders.len();
+        leaders.extend(targets);
+        if leaders.len() == before {
+            break blocks;
+        }
+    }
+}
+
+fn block(ip_register: usize, program: &[Instr], start: usize, leaders: &BTreeSet<usize>) -> Block {
+    let mut regs = initial_registers();
+    let mut ip = start;
+    let exit = loop {
+        step(&mut regs, &program[ip], ip_register, ip);
+        let jumps = program[ip].c == ip_register;
+        if jumps || ip + 1 >= program.len() || leaders.contains(&(ip + 1)) {
+            break next(&regs, ip_register);
+        }
+        ip += 1;
+    };
+    let transfers = regs
+        .iter()
+        .enumerate()
+        .filter(|(r, expr)| *r != ip_register && **expr != Expr::Reg(*r))
+        .map(|(r, expr)| (r, expr.clone()))
+        .collect();
+    Block { start, end: ip, transfers, exit }
+}
+
+#[derive(PartialEq, Debug)]
+pub(super) enum End {
+    Halt,
+    StepLimit,
+    // the next instruction depends on registers in a way that can't be split in two branches
+    UnknownJump(Expr),
+}
+
+// One way through the program, along with the conditions (expected to hold or not) for taking it.
+pub(super) struct Path {
+    conditions: Vec<(Expr, bool)>,
+    registers: Registers,
+    steps: usize,
+    end: End,
+}
+
+impl Path {
+    // The value register 0 must have for the program to take this path, when the path requires it to equal a constant.
+    pub(super) fn r0(&self) -> Option<usize> {
+        self.conditions.iter().find_map(|(condition, holds)| match condition {
+            Expr::Bin(BinOp::Eq, a, b) if *holds => match (a.as_ref(), b.as_ref()) {
+                (Expr::Reg(0), Expr::Const(v)) | (Expr::Const(v), Expr::Reg(0)) => Some(*v),
+                _ => None,
+            },
+            _ => None,
+        })
+    }
+}
+
+impl Display for Path {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let conditions = self
+            .conditions
+            .iter()
+            .map(|(condition, holds)| if *holds { condition.to_string() } else { format!("!{}", condition) })
+            .collect::<Vec<_>>();
+        let registers = self.registers.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+        write!(
+            f,
+            "{:?} after {} steps when {}, with registers [{}]",
+            self.end,
+            self.steps,
+            if conditions.is_empty() { "always".to_string() } else { conditions.join(" && ") },
+            registers.join(", ")
+        )
+    }
+}
+
+// Runs the program from the given registers, forking whenever a jump depends on a comparison.
+//   Branches contradicting the conditions already on the path are pruned; each path runs for at most `max_steps`
+//   and exploration stops after `max_paths` paths are found.
+pub(super) fn explore(ip_register: usize, program: &[Instr], registers: Registers, max_steps: usize, max_paths: usize) -> Vec<Path> {
+    let mut paths = Vec::new();
+    let mut pending = vec![(0, registers, Vec::new(), 0)];
+    while let Some((mut ip, mut regs, mut conditions, mut steps)) = pending.pop() {
+        if paths.len() >= max_paths {
+            break;
+        }
+        let end = loop {
+            let instr = match program.get(ip) {
+                None => break End::Halt,
+                Some(instr) => instr,
+            };
+            if steps >= max_steps {
+                break End::StepLimit;
+            }
+            step(&mut regs, instr, ip_register, ip);
+            steps += 1;
+            ip = match next(&regs, ip_register) {
+                Next::Address(address) => address,
+                Next::Unknown(ip) => break End::UnknownJump(ip),
+                Next::Branch(condition, f, t) => {
+                    let known = conditions.iter().find(|(c, _)| *c == condition).map(|(_, holds)| *holds);
+                    match known {
+                        Some(true) => t,
+                        Some(false) => f,
+                        None => {
+                            // the comparison's outcome is known on either branch
+                            let assume = |regs: &Registers, holds: bool| {
+                                let mut regs = regs.clone();
+                                regs.iter_mut().for_each(|r| *r = r.substitute(&condition, &Expr::Const(holds as usize)));
+                                regs
+                            };
+                            // explore the branch where the comparison fails first
+                            let mut other = conditions.clone();
+                            other.push((condition.clone(), true));
+                            pending.push((t, assume(&regs, true), other, steps));
+                            regs = assume(&regs, false);
+                            conditions.push((condition, false));
+                            f
+                        }
+                    }
+                }
+            };
+            regs[ip_register] = Expr::Const(ip - 1);
+        };
+        paths.push(Path { conditions, registers: regs, steps, end });
+    }
+    paths
+}
+
+// The initial registers of the puzzle: register 0 is unknown and the others start at 0.
+pub(super) fn unknown_r0() -> Registers {
+    let mut regs = initial_registers();
+    (1..6).for_each(|r| regs[r] = Expr::Const(0));
+    regs
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::elfcode::assemble;
+    use super::super::parse;
+
+    #[test]
+    fn test_blocks() {
+        let (cpu, program) = parse(include_str!("input.txt"));
+        let blocks = blocks(cpu.ip_register, &program);
+        let inner_loop = blocks.iter().find(|b| b.start == 18).expect("missing block");
+        assert_eq!("ip=18..=21\n  r4 := (((r1 + 1) * 256) > r2)\n  if (((r1 + 1) * 256) > r2) goto 23 else goto 22", inner_loop.to_string());
+        assert_eq!(None, blocks.iter().find(|b| b.start == 4).map(|b| b.end), "ip=4 is never reached");
+    }
+
+    #[test]
+    fn test_explore() {
+        let source = r#"#ip 5
+        gtri r0 10 r1
+        addr r1 ip ip       ; r0 > 10 halts right away
+        goto small
+        seti 100 0 ip
+small:  muli r0 2 r2
+"#;
+        let (cpu, program) = parse(&assemble(source).unwrap());
+        let paths = explore(cpu.ip_register, &program, unknown_r0(), 100, 10);
+        let paths = paths.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "Halt after 4 steps when !(r0 > 10), with registers [r0, 0, (r0 * 2), 0, 0, 4]",
+                "Halt after 3 steps when (r0 > 10), with registers [r0, 1, 0, 0, 0, 100]",
+            ],
+            paths
+        );
+    }
+
+    #[test]
+    fn test_halting_r0() {
+        let (cpu, program) = parse(include_str!("input.txt"));
+        let paths = explore(cpu.ip_register, &program, unknown_r0(), 100_000, 10);
+        assert_eq!(End::StepLimit, paths[0].end);
+        assert_eq!(vec![Some(6132825)], paths[1..].iter().map(|p| p.r0()).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,170 @@
+use super::sweep::union_area;
+use super::Claim;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+// Number of entries per node of the tree.
+const FANOUT: usize = 8;
+
+// A rectangle of fabric spanning [left, right[ x [top, bottom[
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+struct Rect {
+    left: u32,
+    top: u32,
+    right: u32,
+    bottom: u32,
+}
+
+impl Rect {
+    fn cell(x: u32, y: u32) -> Self {
+        Rect { left: x, top: y, right: x + 1, bottom: y + 1 }
+    }
+
+    fn of(claim: &Claim) -> Self {
+        Rect { left: claim.left(), top: claim.top(), right: claim.right(), bottom: claim.bottom() }
+    }
+
+    fn union(&self, other: &Rect) -> Rect {
+        Rect {
+            left: self.left.min(other.left),
+            top: self.top.min(other.top),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+
+    fn intersects(&self, other: &Rect) -> bool {
+        self.left < other.right && other.left < self.right && self.top < other.bottom && other.top < self.bottom
+    }
+
+    fn area(&self) -> u64 {
+        (self.right - self.left) as u64 * (self.bottom - self.top) as u64
+    }
+}
+
+enum Node {
+    Leaf(Vec<usize>),
+    Inner(Vec<(Rect, Node)>),
+}
+
+// An R-tree over the claims, bulk loaded by sorting them into tiles (Sort-Tile-Recursive).
+pub(super) struct ClaimIndex<'a> {
+    claims: &'a [Claim],
+    root: Option<(Rect, Node)>,
+}
+
+impl<'a> ClaimIndex<'a> {
+    pub(super) fn new(claims: &'a [Claim]) -> Self {
+        let rects = claims.iter().map(Rect::of).collect::<Vec<_>>();
+        let root = if claims.is_empty() { None } else { Some(build(&rects, (0..claims.len()).collect())) };
+        ClaimIndex { claims, root }
+    }
+
+    // indices of the claims covering some of the given rectangle, in order
+    fn search_indices(&self, rect: &Rect) -> Vec<usize> {
+        let mut found = Vec::new();
+        let mut pending = self.root.iter().collect::<Vec<_>>();
+        while let Some((bounds, node)) = pending.pop() {
+            if !bounds.intersects(rect) {
+                continue;
+            }
+            match node {
+                Node::Leaf(claims) => found.extend(claims.iter().filter(|idx| Rect::of(&self.claims[**idx]).intersects(rect))),
+                Node::Inner(children) => pending.extend(children.iter()),
+            }
+        }
+        found.sort();
+        found
+    }
+
+    // the claims covering some of the given rectangle
+    fn search(&self, rect: &Rect) -> Vec<&'a Claim> {
+        self.search_indices(rect).into_iter().map(|idx| &self.claims[idx]).collect()
+    }
+
+    pub(super) fn at(&self, x: u32, y: u32) -> Vec<&'a Claim> {
+        self.search(&Rect::cell(x, y))
+    }
+
+    // the claims overlapping the given one, excluding itself
+    pub(super) fn overlapping(&self, claim: &Claim) -> Vec<&'a Claim> {
+        self.search(&Rect::of(claim)).into_iter().filter(|other| other.id != claim.id).collect()
+    }
+
+    // The largest group of claims connected by overlaps, along with the area they cover.
+    pub(super) fn largest_cluster(&self) -> Option<(Vec<&'a Claim>, u64)> {
+        // union-find over the claims' indices, with path compression and union by size to keep the trees shallow
+        fn find(parents: &mut [usize], i: usize) -> usize {
+            let mut root = i;
+            while parents[root] != root {
+                root = parents[root];
+            }
+            let mut node = i;
+            while parents[node] != root {
+                let next = parents[node];
+                parents[node] = root;
+                node = next;
+            }
+            root
+        }
+        let mut parents = (0..self.claims.len()).collect::<Vec<_>>();
+        let mut sizes = vec![1; self.claims.len()];
+        for (i, claim) in self.claims.iter().enumerate() {
+            for other in self.search_indices(&Rect::of(claim)) {
+                let (a, b) = (find(&mut parents, i), find(&mut parents, other));
+                if a != b {
+                    let (small, large) = if sizes[a] < sizes[b] { (a, b) } else { (b, a) };
+                    parents[small] = large;
+                    sizes[large] += sizes[small];
+                }
+            }
+        }
+
+        let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+        for i in 0..self.claims.len() {
+            let root = find(&mut parents, i);
+            clusters.entry(root).or_default().push(i);
+        }
+        // ties go to the cluster with the earliest claim
+        clusters
+            .into_values()
+            .max_by_key(|cluster| (cluster.len(), Reverse(cluster[0])))
+            .map(|cluster| {
+                let claims = cluster.into_iter().map(|idx| &self.claims[idx]).collect::<Vec<_>>();
+                let area = union_area(claims.iter().cloned());
+                (claims, area)
+            })
+    }
+}
+
+fn build(rects: &[Rect], mut items: Vec<usize>) -> (Rect, Node) {
+    let bounds = items.iter().skip(1).fold(rects[items[0]], |acc, idx| acc.union(&rects[*idx]));
+    if items.len() <= FANOUT {
+        return (bounds, Node::Leaf(items));
+    }
+
+    // split into about FANOUT children: vertical slices sorted by x, each one tiled by y
+    let slices = (FANOUT as f64).sqrt().ceil() as usize;
+    let per_slice = items.len().div_ceil(slices);
+    items.sort_by_key(|idx| rects[*idx].left + rects[*idx].right);
+    let children = items
+        .chunks(per_slice)
+        .flat_map(|slice| {
+            let mut slice = slice.to_vec();
+            slice.sort_by_key(|idx| rects[*idx].top + rects[*idx].bottom);
+            let per_tile = slice.len().div_ceil(slices);
+            slice.chunks(per_tile).map(|tile| build(rects, tile.to_vec())).collect::<Vec<_>>()
+        })
+        .collect();
+    (bounds, Node::Inner(children))
+}
+
+// the square inches claimed by both
+pub(super) fn overlap_area(a: &Claim, b: &Claim) -> u64 {
+    let (a, b) = (Rect::of(a), Rect::of(b));
+    if a.intersects(&b) {
+        Rect { left: a.left.max(b.left), top: a.top.max(b.top), right: a.right.min(b.right), bottom: a.bottom.min(b.bottom) }.area()
+    } else {
+        0
+    }
+}
@@ -1,5 +1,10 @@
 use std::str::FromStr;
-use std::collections::HashSet;
+
+mod index;
+mod sweep;
+
+use index::{overlap_area, ClaimIndex};
+use sweep::Coverage;
 
 #[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
 struct Pt {
@@ -7,15 +12,6 @@ struct Pt {
     top: u16
 }
 
-impl Pt {
-    fn right(&self, by: u16) -> Pt {
-        Pt { left: self.left + by, top: self.top }
-    }
-    fn down(&self, by: u16) -> Pt {
-        Pt { left: self.left, top: self.top + by }
-    }
-}
-
 impl FromStr for Pt {
     type Err = std::num::ParseIntError;
 
@@ -38,16 +34,18 @@ struct Claim {
 }
 
 impl Claim {
-    fn surface(&self) -> HashSet<Pt> {
-        (0..self.width)
-            .flat_map(|right| {
-                let moved = self.orig.right(right);
-                (0..self.height).map(move |down| {
-                    moved.down(down)
-                })
-            })
-            .collect::<HashSet<Pt>>()
-
+    // the claimed cells span [left, right[ x [top, bottom[
+    fn left(&self) -> u32 {
+        self.orig.left as u32
+    }
+    fn right(&self) -> u32 {
+        self.left() + self.width as u32
+    }
+    fn top(&self) -> u32 {
+        self.orig.top as u32
+    }
+    fn bottom(&self) -> u32 {
+        self.top() + self.height as u32
     }
 }
 
@@ -85,28 +83,6 @@ fn parse(input: String) -> Vec<Claim> {
         .collect::<Vec<_>>()
 }
 
-fn intersecting(claims: &Vec<Claim>) -> HashSet<Pt> {
-    let surfaces = &claims
-        .iter()
-        .map(|x| {
-            x.surface()
-        })
-        .collect::<Vec<_>>();
-
-    // TODO: this is super slow
-    let mut intersecting = HashSet::new();
-    for a in surfaces {
-        for b in surfaces {
-            if a == b { continue };
-            a.intersection(&b).for_each(|x| {
-                intersecting.insert(*x);
-            });
-        }
-    }
-
-    intersecting
-}
-
 pub fn mk(input: String) -> Box<dyn crate::Puzzle> {
     Box::new(Puzzle3 { claims: parse(input) })
 }
@@ -118,16 +94,161 @@ pub struct Puzzle3 {
 impl crate::Puzzle for Puzzle3 {
 
     fn part1(&self) -> String {
-        intersecting(&self.claims).len().to_string()
+        Coverage::new(&self.claims).area(2).to_string()
     }
 
     fn part2(&self) -> String {
-        let pts = intersecting(&self.claims);
+        let coverage = Coverage::new(&self.claims);
         self.claims
             .iter()
-            .find_map(|claim| {
-                if claim.surface().intersection(&pts).collect::<HashSet<_>>().is_empty() { Some(claim.id.to_owned()) } else { None }
-            })
+            .enumerate()
+            .find(|(idx, _)| coverage.is_isolated(*idx))
+            .map(|(_, claim)| claim.id.to_owned())
             .expect("no claim is not intersecting")
     }
+
+    // e.g.: `covered 3`, `isolated`, `overlapping 123`, `at 12,34`, `overlap 1 2` or `cluster`
+    fn query(&self, query: &str) -> Result<String, String> {
+        let ids = |claims: Vec<&Claim>| claims.iter().map(|c| format!("#{}", c.id)).collect::<Vec<_>>().join(" ");
+        let claim = |id: &str| {
+            let id = id.trim_start_matches('#');
+            self.claims.iter().find(|c| c.id == id).ok_or_else(|| format!("unknown claim #{}", id))
+        };
+        match query.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["covered", k] => {
+                match u32::from_str(k) {
+                    Ok(0) => Err("the fabric has to be covered by at least 1 claim".to_string()),
+                    Ok(k) => Ok(Coverage::new(&self.claims).area(k).to_string()),
+                    Err(_) => Err(format!("invalid number of claims {}", k)),
+                }
+            }
+            ["isolated"] => {
+                let coverage = Coverage::new(&self.claims);
+                Ok(ids(self.claims.iter().enumerate().filter(|(idx, _)| coverage.is_isolated(*idx)).map(|(_, claim)| claim).collect()))
+            }
+            ["overlapping", id] => Ok(ids(ClaimIndex::new(&self.claims).overlapping(claim(id)?))),
+            ["at", pt] => {
+                let pt = Pt::from_str(pt).map_err(|_| format!("invalid position {}, expected x,y", pt))?;
+                Ok(ids(ClaimIndex::new(&self.claims).at(pt.left as u32, pt.top as u32)))
+            }
+            ["overlap", a, b] => Ok(overlap_area(claim(a)?, claim(b)?).to_string()),
+            ["cluster"] => match ClaimIndex::new(&self.claims).largest_cluster() {
+                None => Err("there are no claims".to_string()),
+                Some((claims, area)) => Ok(format!("{} claims covering {} square inches: {}", claims.len(), area, ids(claims))),
+            },
+            _ => Err(format!("unknown query {}, expected one of: covered <k>, isolated, overlapping <id>, at <x,y>, overlap <id> <id>, cluster", query)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Puzzle;
+    use std::collections::{HashMap, HashSet};
+
+    const EXAMPLE: &str = r#"#1 @ 1,3: 4x4
+#2 @ 3,1: 4x4
+#3 @ 5,5: 2x2"#;
+
+    #[test]
+    fn test_example() {
+        let claims = parse(EXAMPLE.to_string());
+        let coverage = Coverage::new(&claims);
+        assert_eq!(32, coverage.area(1));
+        assert_eq!(4, coverage.area(2));
+        assert_eq!(0, coverage.area(3));
+        assert_eq!(vec![false, false, true], (0..claims.len()).map(|idx| coverage.is_isolated(idx)).collect::<Vec<_>>());
+
+        let puzzle = Puzzle3 { claims };
+        assert_eq!(Ok("4".to_string()), puzzle.query("covered 2"));
+        assert_eq!(Err("the fabric has to be covered by at least 1 claim".to_string()), puzzle.query("covered 0"));
+    }
+
+    #[test]
+    fn test_large() {
+        // too many distinct edges for a grid of them
+        let mut seed = 42u64;
+        let mut random = |max: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) % max
+        };
+        let claims = (0..100_000)
+            .map(|id| format!("#{} @ {},{}: {}x{}", id, random(60_000), random(60_000), 1 + random(100), 1 + random(100)))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let claims = parse(claims);
+        let coverage = Coverage::new(&claims);
+        let (once, twice) = (coverage.area(1), coverage.area(2));
+        assert!(once > twice && twice > 0);
+        assert!(once <= claims.iter().map(|c| c.width as u64 * c.height as u64).sum::<u64>());
+        assert!((0..claims.len()).any(|idx| coverage.is_isolated(idx)));
+        let (cluster, area) = ClaimIndex::new(&claims).largest_cluster().unwrap();
+        assert!(cluster.len() > 1 && area <= once);
+
+        // a single cluster chaining all the claims, each one overlapping the next
+        let chain = parse((0..100_000).map(|id| format!("#{} @ {},{}: 2x2", id, id / 2, id % 2)).collect::<Vec<_>>().join("\n"));
+        let (cluster, area) = ClaimIndex::new(&chain).largest_cluster().unwrap();
+        assert_eq!((100_000, 50_001 * 3), (cluster.len(), area));
+    }
+
+    #[test]
+    fn test_empty_claims() {
+        let claims = parse("#1 @ 1,1: 4x4\n#2 @ 2,2: 0x3\n#3 @ 5,1: 2x2".to_string());
+        let coverage = Coverage::new(&claims);
+        assert_eq!(20, coverage.area(1));
+        assert_eq!(0, coverage.area(2));
+        assert_eq!(vec![true, true, true], (0..claims.len()).map(|idx| coverage.is_isolated(idx)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_index() {
+        let claims = parse(EXAMPLE.to_string());
+        let index = ClaimIndex::new(&claims);
+        let ids = |claims: Vec<&Claim>| claims.iter().map(|c| c.id.clone()).collect::<Vec<_>>();
+        assert_eq!(vec!["2"], ids(index.overlapping(&claims[0])));
+        assert!(index.overlapping(&claims[2]).is_empty());
+        assert_eq!(vec!["1", "2"], ids(index.at(3, 3)));
+        assert_eq!(vec!["3"], ids(index.at(6, 6)));
+        assert!(index.at(0, 0).is_empty());
+        assert_eq!(4, overlap_area(&claims[0], &claims[1]));
+        assert_eq!(0, overlap_area(&claims[0], &claims[2]));
+
+        let (cluster, area) = index.largest_cluster().unwrap();
+        assert_eq!(vec!["1", "2"], ids(cluster));
+        assert_eq!(28, area);
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        let claims = parse(include_str!("input.txt").to_string());
+        let mut counts = HashMap::new();
+        for claim in &claims {
+            for x in claim.left()..claim.right() {
+                for y in claim.top()..claim.bottom() {
+                    *counts.entry((x, y)).or_insert(0u32) += 1;
+                }
+            }
+        }
+
+        let coverage = Coverage::new(&claims);
+        for k in 1..=4 {
+            assert_eq!(counts.values().filter(|c| **c >= k).count() as u64, coverage.area(k));
+        }
+
+        let index = ClaimIndex::new(&claims);
+        for (idx, claim) in claims.iter().enumerate() {
+            let expected = claims.iter().filter(|other| other.id != claim.id && overlap_area(claim, other) > 0).map(|c| &c.id).collect::<Vec<_>>();
+            let overlapping = index.overlapping(claim);
+            assert_eq!(expected, overlapping.iter().map(|c| &c.id).collect::<Vec<_>>());
+            assert_eq!(overlapping.is_empty(), coverage.is_isolated(idx), "claim #{}", claim.id);
+        }
+        let (cluster, area) = index.largest_cluster().unwrap();
+        let cells = cluster.iter().flat_map(|c| (c.left()..c.right()).flat_map(move |x| (c.top()..c.bottom()).map(move |y| (x, y))));
+        assert_eq!(cells.collect::<HashSet<_>>().len() as u64, area);
+
+        for (x, y) in [(0, 0), (500, 500), (120, 380), (999, 999)] {
+            assert_eq!(counts.get(&(x, y)).cloned().unwrap_or(0) as usize, index.at(x, y).len());
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,203 @@
+use super::Claim;
+
+// A claim's left (+1) or right (-1) edge as (x, top, bottom, delta), with indices into the sorted ys.
+type Edge = (u32, usize, usize, i32);
+
+// How many claims cover each part of the fabric, swept left to right along the claims' vertical edges.
+//   Between two consecutive edges the claims covering each row don't change, so the covered length of the sweep line
+//   is kept in a segment tree over the claims' horizontal edges, in O(n log n) overall rather than for every square inch.
+pub(super) struct Coverage {
+    // the claims' top and bottom edges, sorted and deduplicated
+    ys: Vec<u32>,
+    // the claims' vertical edges, sorted by x
+    edges: Vec<Edge>,
+    // whether no other claim overlaps each one, in the order they were given
+    isolated: Vec<bool>,
+}
+
+impl Coverage {
+    pub(super) fn new<'a>(claims: impl IntoIterator<Item = &'a Claim>) -> Self {
+        let claims = claims.into_iter().collect::<Vec<_>>();
+        let (ys, edges) = edges(&claims);
+        let isolated = isolated(&claims, &ys);
+        Coverage { ys, edges, isolated }
+    }
+
+    // the square inches of fabric covered by at least k claims, k being 1 or more, in O(n k log n)
+    pub(super) fn area(&self, k: u32) -> u64 {
+        if k as usize > self.isolated.len() {
+            return 0;
+        }
+        swept_area(&self.ys, &self.edges, k as usize)
+    }
+
+    // whether no other claim overlaps the claim given at that position
+    pub(super) fn is_isolated(&self, idx: usize) -> bool {
+        self.isolated[idx]
+    }
+}
+
+// The square inches of fabric covered by any of the claims, without looking for isolated claims.
+pub(super) fn union_area<'a>(claims: impl IntoIterator<Item = &'a Claim>) -> u64 {
+    let (ys, edges) = edges(&claims.into_iter().collect::<Vec<_>>());
+    swept_area(&ys, &edges, 1)
+}
+
+// The claims' top and bottom edges, sorted and deduplicated, along with their vertical edges sorted by x.
+fn edges(claims: &[&Claim]) -> (Vec<u32>, Vec<Edge>) {
+    let mut ys = claims.iter().flat_map(|c| vec![c.top(), c.bottom()]).collect::<Vec<_>>();
+    ys.sort();
+    ys.dedup();
+    let y_idx = |y: u32| ys.binary_search(&y).unwrap();
+
+    let mut edges = claims
+        .iter()
+        .flat_map(|c| {
+            let (t, b) = (y_idx(c.top()), y_idx(c.bottom()));
+            vec![(c.left(), t, b, 1), (c.right(), t, b, -1)]
+        })
+        .collect::<Vec<_>>();
+    // the claims ending at some x don't overlap the ones starting there
+    edges.sort_by_key(|(x, _, _, delta)| (*x, *delta));
+    (ys, edges)
+}
+
+fn swept_area(ys: &[u32], edges: &[Edge], k: usize) -> u64 {
+    let mut tree = CoverTree::new(ys, k);
+    let mut area = 0;
+    let mut last_x = edges.first().map(|e| e.0).unwrap_or(0);
+    for (x, top, bottom, delta) in edges.iter() {
+        area += (x - last_x) as u64 * tree.covered();
+        tree.add(*top, *bottom, *delta);
+        last_x = *x;
+    }
+    area
+}
+
+// Counts of how many times each part of the y axis is covered, as a segment tree over the elementary intervals between ys.
+struct CoverTree<'a> {
+    ys: &'a [u32],
+    k: usize,
+    // the claims covering the whole span of each node, without being counted in its children
+    cover: Vec<u32>,
+    // the length covered at least 0..=k times within the span of each node, k + 1 entries per node
+    covered: Vec<u64>,
+}
+
+impl<'a> CoverTree<'a> {
+    fn new(ys: &'a [u32], k: usize) -> Self {
+        let nodes = 4 * ys.len().max(1);
+        CoverTree { ys, k, cover: vec![0; nodes], covered: vec![0; nodes * (k + 1)] }
+    }
+
+    // the length of the sweep line covered at least k times
+    fn covered(&self) -> u64 {
+        self.covered[(self.k + 1) + self.k]
+    }
+
+    fn add(&mut self, top: usize, bottom: usize, delta: i32) {
+        if self.ys.len() > 1 {
+            self.update(1, 0, self.ys.len() - 1, top, bottom, delta);
+        }
+    }
+
+    // adds delta to the elementary intervals [top, bottom[ within the node spanning [lo, hi[
+    fn update(&mut self, node: usize, lo: usize, hi: usize, top: usize, bottom: usize, delta: i32) {
+        if bottom <= lo || hi <= top {
+            return;
+        }
+        if top <= lo && hi <= bottom {
+            self.cover[node] = (self.cover[node] as i32 + delta) as u32;
+        } else {
+            let mid = (lo + hi) / 2;
+            self.update(2 * node, lo, mid, top, bottom, delta);
+            self.update(2 * node + 1, mid, hi, top, bottom, delta);
+        }
+        self.pull(node, lo, hi);
+    }
+
+    fn pull(&mut self, node: usize, lo: usize, hi: usize) {
+        let cover = self.cover[node] as usize;
+        let stride = self.k + 1;
+        for j in 0..=self.k {
+            self.covered[node * stride + j] = if cover >= j {
+                (self.ys[hi] - self.ys[lo]) as u64
+            } else if hi - lo == 1 {
+                0
+            } else {
+                // the rest of the coverage has to come from below
+                self.covered[2 * node * stride + j - cover] + self.covered[(2 * node + 1) * stride + j - cover]
+            };
+        }
+    }
+}
+
+// For each claim, whether no other claim overlaps it.
+//   Claim d overlaps c when their y spans overlap and d starts before c ends without ending before c starts,
+//   so sweeping over the x edges it's the claims overlapping in y started before c's right edge, minus the ones
+//   that ended by its left edge: each side counted with Fenwick trees of the ended or started claims' y edges.
+fn isolated(claims: &[&Claim], ys: &[u32]) -> Vec<bool> {
+    let y_idx = |y: u32| ys.binary_search(&y).unwrap();
+    // (x, is a start, claim), ends at some x come first as they don't overlap the claims starting there
+    //   claims without any area overlap nothing, and would end before starting
+    let mut events = claims
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.width > 0 && c.height > 0)
+        .flat_map(|(idx, c)| vec![(c.left(), true, idx), (c.right(), false, idx)])
+        .collect::<Vec<_>>();
+    events.sort();
+
+    // claims started so far, and claims ended so far, as the counts of their top and bottom edges
+    let (mut started, mut ended) = ((Fenwick::new(ys.len()), Fenwick::new(ys.len())), (Fenwick::new(ys.len()), Fenwick::new(ys.len())));
+    let (mut n_started, mut n_ended) = (0, 0);
+    // the claims intersecting [t, b[ in y among some, i.e. all but the ones ending above t or starting from b
+    let intersecting = |(tops, bottoms): &(Fenwick, Fenwick), n: i64, t: usize, b: usize| n - bottoms.prefix(t + 1) - (n - tops.prefix(b));
+    let mut overlaps = vec![0i64; claims.len()];
+    for (_, is_start, idx) in events {
+        let (t, b) = (y_idx(claims[idx].top()), y_idx(claims[idx].bottom()));
+        if is_start {
+            overlaps[idx] -= intersecting(&ended, n_ended, t, b);
+            started.0.add(t);
+            started.1.add(b);
+            n_started += 1;
+        } else {
+            // the claim itself is among the started ones
+            overlaps[idx] += intersecting(&started, n_started, t, b) - 1;
+            ended.0.add(t);
+            ended.1.add(b);
+            n_ended += 1;
+        }
+    }
+    overlaps.into_iter().map(|n| n == 0).collect()
+}
+
+// Counts of positions, with prefix sums in O(log n).
+struct Fenwick {
+    tree: Vec<i64>,
+}
+
+impl Fenwick {
+    fn new(size: usize) -> Self {
+        Fenwick { tree: vec![0; size + 1] }
+    }
+
+    fn add(&mut self, pos: usize) {
+        let mut i = pos + 1;
+        while i < self.tree.len() {
+            self.tree[i] += 1;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    // how many were added at positions before `end`
+    fn prefix(&self, end: usize) -> i64 {
+        let mut i = end;
+        let mut sum = 0;
+        while i > 0 {
+            sum += self.tree[i];
+            i -= i & i.wrapping_neg();
+        }
+        sum
+    }
+}
@@ -1,26 +1,124 @@
 use std::str::FromStr;
+use std::fmt;
+use std::ops::Range;
 use regex::Regex;
 use crate::puzzle4::What::{FallAsleep, WakeUp, ShiftStart};
 use std::collections::HashMap;
 use std::convert::TryInto;
 
-#[derive(Eq, PartialEq, PartialOrd, Debug)]
+mod report;
+use report::Format;
+
+const MINUTES_PER_DAY: i64 = 24 * 60;
+
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Copy, Debug)]
+struct Date {
+    year: i32,
+    month: u8,
+    day: u8
+}
+
+impl Date {
+    fn new(year: i32, month: u8, day: u8) -> Result<Self, String> {
+        let is_leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let days_in_month = match month {
+            2 if is_leap => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            1 ..= 12 => 31,
+            _ => return Err(format!("invalid month {}", month))
+        };
+        if day < 1 || day > days_in_month {
+            return Err(format!("invalid day {} for {}-{:02}", day, year, month));
+        }
+        Ok(Date { year, month, day })
+    }
+
+    // Days since 1970-01-01 in the proleptic Gregorian calendar, see http://howardhinnant.github.io/date_algorithms.html
+    fn days(&self) -> i64 {
+        let (month, day) = (self.month as i64, self.day as i64);
+        // years start in March so that the leap day is the last one
+        let year = self.year as i64 - if month <= 2 { 1 } else { 0 };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    fn from_days(days: i64) -> Self {
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = (shifted_month + 2) % 12 + 1;
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        Date { year: year as i32, month: month as u8, day: day as u8 }
+    }
+
+    fn next(&self) -> Self {
+        Date::from_days(self.days() + 1)
+    }
+}
+
+impl FromStr for Date {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let re = Regex::new(r"^(\d{4})-(\d{2})-(\d{2})$").unwrap();
+        let caps = re.captures(s).ok_or_else(|| format!("invalid date {}", s))?;
+        Date::new(caps[1].parse().unwrap(), caps[2].parse().unwrap(), caps[3].parse().unwrap())
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Copy, Debug)]
 struct Ts {
-    day: String,
+    date: Date,
     hour: u8,
     minute: u8
 }
 
+impl Ts {
+    // Minutes since 1970-01-01 00:00
+    fn minutes(&self) -> i64 {
+        self.date.days() * MINUTES_PER_DAY + self.hour as i64 * 60 + self.minute as i64
+    }
+
+    fn from_minutes(minutes: i64) -> Self {
+        let date = Date::from_days(minutes.div_euclid(MINUTES_PER_DAY));
+        let minutes = minutes.rem_euclid(MINUTES_PER_DAY);
+        Ts { date, hour: (minutes / 60) as u8, minute: (minutes % 60) as u8 }
+    }
+}
+
 impl FromStr for Ts {
-    type Err = std::num::ParseIntError;
+    type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let re = Regex::new(r"^\[(\d{4}-\d{2}-\d{2}) (\d{2}):(\d{2})\]$").unwrap();
-        let caps = re.captures(s).expect("invalid date input");
-        let day = caps[1].to_string();
-        let hour = u8::from_str(&caps[2])?;
-        let minute = u8::from_str(&caps[3])?;
-        Ok(Ts { day, hour, minute })
+        let caps = re.captures(s).ok_or_else(|| format!("invalid timestamp {}", s))?;
+        let date = Date::from_str(&caps[1])?;
+        let hour = u8::from_str(&caps[2]).unwrap();
+        let minute = u8::from_str(&caps[3]).unwrap();
+        if hour >= 24 || minute >= 60 {
+            return Err(format!("invalid time {}:{}", &caps[2], &caps[3]));
+        }
+        Ok(Ts { date, hour, minute })
+    }
+}
+
+impl fmt::Display for Ts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{} {:02}:{:02}]", self.date, self.hour, self.minute)
     }
 }
 
@@ -32,7 +130,7 @@ enum What {
 }
 
 impl FromStr for What {
-    type Err = std::num::ParseIntError;
+    type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let re = Regex::new(r"^Guard #(\d+) begins shift$").unwrap();
@@ -40,8 +138,8 @@ impl FromStr for What {
             "falls asleep" => Ok(FallAsleep),
             "wakes up" => Ok(WakeUp),
             _ => {
-                let caps = re.captures(s).expect("invalid event");
-                let id = u32::from_str(&caps[1])?;
+                let caps = re.captures(s).ok_or_else(|| format!("invalid event {}", s))?;
+                let id = u32::from_str(&caps[1]).map_err(|_| format!("invalid guard id {}", &caps[1]))?;
                 Ok(ShiftStart(id))
             }
         }
@@ -55,11 +153,11 @@ struct Event {
 }
 
 impl FromStr for Event {
-    type Err = std::num::ParseIntError;
+    type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let re = Regex::new(r"^(\[.*\]) (.*)$").unwrap();
-        let caps = re.captures(s).expect("invalid line");
+        let caps = re.captures(s).ok_or_else(|| format!("invalid line {}", s))?;
         let ts = Ts::from_str(&caps[1])?;
         let event = What::from_str(&caps[2])?;
 
@@ -67,10 +165,29 @@ impl FromStr for Event {
     }
 }
 
+// A guard's shift, dated by the night it covers: shifts starting in the evening are for the next day's midnight hour.
 #[derive(Clone, PartialEq, Debug)]
 struct Shift {
-    day: String,
-    sleeping: Vec<u16>
+    date: Date,
+    // from the minute the guard falls asleep up to the one they wake up
+    naps: Vec<Range<Ts>>
+}
+
+impl Shift {
+    fn new(start: Ts) -> Self {
+        let date = if start.hour >= 12 { start.date.next() } else { start.date };
+        Shift { date, naps: Vec::new() }
+    }
+
+    // every minute the guard was asleep
+    fn asleep(&self) -> impl Iterator<Item = Ts> + '_ {
+        self.naps.iter().flat_map(|nap| (nap.start.minutes() .. nap.end.minutes()).map(Ts::from_minutes))
+    }
+
+    // the minutes of the night's midnight hour the guard was asleep, the only hour the strategies look at
+    fn midnight_hour(&self) -> impl Iterator<Item = u8> + '_ {
+        self.asleep().filter(move |ts| ts.date == self.date && ts.hour == 0).map(|ts| ts.minute)
+    }
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -80,13 +197,11 @@ struct Guard {
 }
 
 impl Guard {
+    // how many times the guard was asleep at each minute of the midnight hour
     fn alertness(&self) -> Vec<u16> {
         let mut a: [u16; 60] = [0; 60];
-        for s in self.shifts.iter().map(|x| &x.sleeping) {
-            for i in 0..60 {
-                let value = s.get(i).expect("invalid shift");
-                a[i] += *value;
-            }
+        for minute in self.shifts.iter().flat_map(|s| s.midnight_hour()) {
+            a[minute as usize] += 1;
         }
 
         a.to_vec()
@@ -95,8 +210,8 @@ impl Guard {
     fn summary(&self) -> Summary {
         let a = self.alertness();
 
-        // total sleep time for all shifts.
-        let total = a.iter().map(|x| *x as u32).sum();
+        // total sleep time for all shifts, whatever the hour.
+        let total = self.shifts.iter().map(|s| s.asleep().count() as u32).sum();
 
         // captures the minute the guard was alseep the most
         let mut worse = WorseMinute { minute: 0, sleeping: 0 };
@@ -124,95 +239,121 @@ struct WorseMinute {
     sleeping: u16
 }
 
-fn parse(input: String) -> Vec<Event> {
+fn parse(input: &str) -> Result<Vec<Event>, String> {
     let mut events = input.lines()
-        .map(|x| Event::from_str(x).unwrap_or_else(|_| panic!("invalid line {}", x)))
-        .collect::<Vec<_>>();
-    events.sort_by(|a,b| a.ts.partial_cmp(&b.ts).unwrap());
-    events
+        .map(Event::from_str)
+        .collect::<Result<Vec<_>, _>>()?;
+    // the sort is stable, so events logged at the same minute keep their order
+    events.sort_by_key(|e| e.ts);
+    Ok(events)
 }
 
-fn to_shifts(events: &Vec<Event>) -> Vec<Guard> {
+fn to_shifts(events: &[Event]) -> Result<Vec<Guard>, String> {
     let mut guard_shifts: HashMap<u32, Vec<Shift>> = HashMap::new();
 
-    match events.first() {
-        Some(Event { ts, event: What::ShiftStart(id)}) => {
-            let mut current_guard = id;
-            let mut shift = [0; 60];
-            let mut shift_day = ts.day.clone();
-            for event in events.iter().skip(1) {
-                match event {
-                    Event { ts, event: What::FallAsleep } => {
-                        for i in ts.minute .. 60 {
-                            shift[i as usize] = 1;
-                        }
-                    },
-                    Event { ts, event: What::WakeUp } => {
-                        for i in ts.minute .. 60 {
-                            shift[i as usize] = 0;
-                        }
-                    },
-                    Event { ts, event: What::ShiftStart(id) } => {
-                        let current_shift = Shift { day: shift_day, sleeping: shift.to_vec() };
-                        guard_shifts.entry(*current_guard).or_insert(Vec::new()).push(current_shift);
-                        current_guard = id;
-                        shift = [0; 60];
-                        shift_day = ts.day.clone();
+    // the guard on duty, their shift so far and since when they're asleep
+    let mut current: Option<(u32, Shift, Option<Ts>)> = None;
+    for Event { ts, event } in events {
+        match event {
+            ShiftStart(id) => {
+                if let Some((guard, shift, asleep)) = current.take() {
+                    if let Some(since) = asleep {
+                        return Err(format!("{} guard #{} begins shift while guard #{} is asleep since {}", ts, id, guard, since));
                     }
+                    guard_shifts.entry(guard).or_default().push(shift);
                 }
+                current = Some((*id, Shift::new(*ts), None));
+            },
+            FallAsleep => match &mut current {
+                None => return Err(format!("{} a guard falls asleep before the first shift", ts)),
+                Some((guard, _, Some(since))) => return Err(format!("{} guard #{} falls asleep but is already asleep since {}", ts, guard, since)),
+                Some((_, _, asleep)) => *asleep = Some(*ts),
+            },
+            WakeUp => match &mut current {
+                None => return Err(format!("{} a guard wakes up before the first shift", ts)),
+                Some((guard, _, None)) => return Err(format!("{} guard #{} wakes up but isn't asleep", ts, guard)),
+                Some((_, shift, asleep)) => shift.naps.push(asleep.take().unwrap() .. *ts),
             }
-            let current_shift = Shift { day: shift_day, sleeping: shift.to_vec() };
-            guard_shifts.entry(*current_guard).or_insert(Vec::new()).push(current_shift);
-        },
-        Some(event) => panic!("inalid first event {:?}", event),
-        None => unimplemented!() // TODO
+        }
+    }
+    if let Some((guard, shift, asleep)) = current {
+        if let Some(since) = asleep {
+            return Err(format!("guard #{} is still asleep at the end of the log, since {}", guard, since));
+        }
+        guard_shifts.entry(guard).or_default().push(shift);
     }
 
-    guard_shifts.iter()
-        .map(|(g,s)| Guard { id: *g, shifts: s.clone()} )
-        .collect::<Vec<_>>()
+    Ok(guard_shifts.into_iter()
+        .map(|(id, shifts)| Guard { id, shifts })
+        .collect::<Vec<_>>())
 }
 
 pub fn mk(input: String) -> Box<dyn crate::Puzzle> {
-    Box::new(Puzzle4 { events: parse(input) })
+    let guards = parse(&input)
+        .and_then(|events| to_shifts(&events))
+        .unwrap_or_else(|e| panic!("invalid guard log: {}", e));
+    Box::new(Puzzle4 { guards })
 }
 pub struct Puzzle4 {
-    events: Vec<Event>
+    guards: Vec<Guard>
 }
 
 impl crate::Puzzle for Puzzle4 {
 
     fn part1(&self) -> String {
-        let guard_shifts = to_shifts(&self.events);
-
-        let worse = guard_shifts.iter()
+        let worse = self.guards.iter()
             .map(|x| x.summary())
             .max_by(|a,b| a.total_sleep.cmp(&b.total_sleep)).expect("no shifts");
 
-        println!("{:?}", worse);
         (worse.id * worse.worse_minute.minute as u32).to_string()
     }
 
     fn part2(&self) -> String {
-        let guard_shifts = to_shifts(&self.events);
-
-        let worse = guard_shifts.iter()
+        let worse = self.guards.iter()
             .map(|x| x.summary())
             .max_by(|a,b| a.worse_minute.sleeping.cmp(&b.worse_minute.sleeping)).expect("no shifts");
 
-        println!("{:?}", worse);
         (worse.id * worse.worse_minute.minute as u32).to_string()
     }
+
+    fn query(&self, query: &str) -> Result<String, String> {
+        let format = |format: Option<&&str>| format.map_or(Ok(Format::Text), |f| Format::from_str(f));
+        match query.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["report", rest @ ..] if rest.len() <= 1 => Ok(report::guards(&self.guards, format(rest.first())?)),
+            ["chart", rest @ ..] if rest.len() <= 1 => Ok(report::chart(&self.guards, format(rest.first())?)),
+            _ => Err(format!("unknown query {}, expected one of: report [text|csv], chart [text|csv]", query))
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::ops::Range;
+
+    fn ts(s: &str) -> Ts {
+        Ts::from_str(s).unwrap()
+    }
 
     #[test]
     fn test_ts_from_str() {
-        assert_eq!(Ts::from_str("[1518-04-22 00:56]"), Ok(Ts { day: "1518-04-22".to_string(), hour: 0, minute: 56} ));
+        let date = Date { year: 1518, month: 4, day: 22 };
+        assert_eq!(Ts::from_str("[1518-04-22 00:56]"), Ok(Ts { date, hour: 0, minute: 56 }));
+        assert_eq!(Ts::from_str("[1518-02-29 00:56]"), Err("invalid day 29 for 1518-02".to_string()));
+        assert_eq!(Ts::from_str("[1518-04-22 24:00]"), Err("invalid time 24:00".to_string()));
+        assert_eq!(Ts::from_str("1518-04-22 00:56"), Err("invalid timestamp 1518-04-22 00:56".to_string()));
+    }
+
+    #[test]
+    fn test_date_arithmetic() {
+        assert_eq!(0, Date { year: 1970, month: 1, day: 1 }.days());
+        assert_eq!(Date { year: 1519, month: 1, day: 1 }, Date { year: 1518, month: 12, day: 31 }.next());
+        assert_eq!(Date { year: 1600, month: 2, day: 29 }, Date { year: 1600, month: 2, day: 28 }.next());
+        assert_eq!(Date { year: 1700, month: 3, day: 1 }, Date { year: 1700, month: 2, day: 28 }.next());
+        let start = Date { year: 1518, month: 1, day: 1 }.days();
+        for days in start .. start + 2000 {
+            assert_eq!(days, Date::from_days(days).days());
+        }
+        assert_eq!(ts("[1518-03-01 00:00]"), Ts::from_minutes(ts("[1518-02-28 23:59]").minutes() + 1));
     }
 
     #[test]
@@ -225,54 +366,90 @@ mod tests {
 
     #[test]
     fn test_event_from_str() {
-        fn ts() -> Ts { Ts { day: "1518-04-22".to_string(), hour: 0, minute: 56 } }
-        assert_eq!(Event::from_str("[1518-04-22 00:56] falls asleep"), Ok(Event { ts: ts(), event: FallAsleep }));
-        assert_eq!(Event::from_str("[1518-04-22 00:56] wakes up"), Ok(Event { ts: ts(), event: WakeUp }));
-        assert_eq!(Event::from_str("[1518-04-22 00:56] Guard #3491 begins shift"), Ok(Event { ts: ts(), event: ShiftStart(3491) }));
+        assert_eq!(Event::from_str("[1518-04-22 00:56] falls asleep"), Ok(Event { ts: ts("[1518-04-22 00:56]"), event: FallAsleep }));
+        assert_eq!(Event::from_str("[1518-04-22 00:56] wakes up"), Ok(Event { ts: ts("[1518-04-22 00:56]"), event: WakeUp }));
+        assert_eq!(Event::from_str("[1518-04-22 00:56] Guard #3491 begins shift"), Ok(Event { ts: ts("[1518-04-22 00:56]"), event: ShiftStart(3491) }));
     }
 
     #[test]
     fn test_to_shifts() {
-        let events = vec![
-            Event { ts: Ts { day: "1518-02-14".to_string(), hour: 23, minute: 52 }, event: ShiftStart(2939) },
-            Event { ts: Ts { day: "1518-02-15".to_string(), hour: 0, minute: 0 }, event: FallAsleep },
-            Event { ts: Ts { day: "1518-02-15".to_string(), hour: 0, minute: 41 }, event: WakeUp },
-
-            Event { ts: Ts { day: "1518-02-15".to_string(), hour: 23, minute: 57 }, event: ShiftStart(131) },
-            Event { ts: Ts { day: "1518-02-16".to_string(), hour: 0, minute: 7 }, event: FallAsleep },
-            Event { ts: Ts { day: "1518-02-16".to_string(), hour: 0, minute: 44 }, event: WakeUp },
-
-            Event { ts: Ts { day: "1518-02-17".to_string(), hour: 0, minute: 0 }, event: ShiftStart(2399) },
-            Event { ts: Ts { day: "1518-02-17".to_string(), hour: 0, minute: 13 }, event: FallAsleep },
-            Event { ts: Ts { day: "1518-02-17".to_string(), hour: 0, minute: 36 }, event: WakeUp },
-
-            Event { ts: Ts { day: "1518-02-17".to_string(), hour: 23, minute: 59 }, event: ShiftStart(3373) },
-            Event { ts: Ts { day: "1518-02-18".to_string(), hour: 0, minute: 6 }, event: FallAsleep },
-            Event { ts: Ts { day: "1518-02-18".to_string(), hour: 0, minute: 19 }, event: WakeUp },
-            Event { ts: Ts { day: "1518-02-18".to_string(), hour: 0, minute: 46 }, event: FallAsleep },
-            Event { ts: Ts { day: "1518-02-18".to_string(), hour: 0, minute: 51 }, event: WakeUp },
-            Event { ts: Ts { day: "1518-02-18".to_string(), hour: 0, minute: 56 }, event: FallAsleep },
-            Event { ts: Ts { day: "1518-02-18".to_string(), hour: 0, minute: 58 }, event: WakeUp }
-        ];
-
-        let mut guards = to_shifts(&events);
+        let events = parse(r"[1518-02-14 23:52] Guard #2939 begins shift
+[1518-02-15 00:00] falls asleep
+[1518-02-15 00:41] wakes up
+[1518-02-15 23:57] Guard #131 begins shift
+[1518-02-16 00:07] falls asleep
+[1518-02-16 00:44] wakes up
+[1518-02-17 00:00] Guard #2399 begins shift
+[1518-02-17 00:13] falls asleep
+[1518-02-17 00:36] wakes up
+[1518-02-17 23:59] Guard #3373 begins shift
+[1518-02-18 00:06] falls asleep
+[1518-02-18 00:19] wakes up
+[1518-02-18 00:46] falls asleep
+[1518-02-18 00:51] wakes up
+[1518-02-18 00:56] falls asleep
+[1518-02-18 00:58] wakes up").unwrap();
+
+        let mut guards = to_shifts(&events).unwrap();
         guards.sort_by(|a,b| a.id.cmp(&b.id));
         assert_eq!(guards.len(), 4 as usize);
 
-        fn assert_guard(guards: &Vec<Guard>, id: u32, date: String, asleep: Vec<Range<usize>>) -> () {
+        fn assert_guard(guards: &Vec<Guard>, id: u32, date: &str, asleep: Vec<Range<usize>>) -> () {
             let mut sleeping = [0; 60];
             for a in asleep {
                 for i in a {
                     sleeping[i] = 1;
                 }
             }
-            let guard = guards.iter().find(|g| g.id == id);
-            assert_eq!(guard, Some(&Guard { id, shifts: vec![Shift { day: date, sleeping: sleeping.to_vec()} ]}));
+            let guard = guards.iter().find(|g| g.id == id).unwrap();
+            assert_eq!(guard.shifts.iter().map(|s| s.date.to_string()).collect::<Vec<_>>(), vec![date.to_string()]);
+            assert_eq!(guard.alertness(), sleeping.to_vec());
         }
 
-        assert_guard(&guards, 2939, "1518-02-14".to_string(), vec![0..41]);
-        assert_guard(&guards, 2399, "1518-02-17".to_string(), vec![13..36]);
-        assert_guard(&guards, 131, "1518-02-15".to_string(), vec![7..44]);
-        assert_guard(&guards, 3373, "1518-02-17".to_string(), vec![6..19, 46..51, 56..58]);
+        assert_guard(&guards, 2939, "1518-02-15", vec![0..41]);
+        assert_guard(&guards, 2399, "1518-02-17", vec![13..36]);
+        assert_guard(&guards, 131, "1518-02-16", vec![7..44]);
+        assert_guard(&guards, 3373, "1518-02-18", vec![6..19, 46..51, 56..58]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_sleep_across_hours() {
+        let events = parse(r"[1518-12-31 22:10] Guard #10 begins shift
+[1518-12-31 23:50] falls asleep
+[1519-01-01 00:10] wakes up
+[1519-01-01 01:05] falls asleep
+[1519-01-01 01:15] wakes up").unwrap();
+        let guards = to_shifts(&events).unwrap();
+        assert_eq!(guards, vec![Guard { id: 10, shifts: vec![Shift {
+            date: Date { year: 1519, month: 1, day: 1 },
+            naps: vec![ts("[1518-12-31 23:50]") .. ts("[1519-01-01 00:10]"), ts("[1519-01-01 01:05]") .. ts("[1519-01-01 01:15]")]
+        }]}]);
+        assert_eq!(30, guards[0].summary().total_sleep);
+
+        // only the minutes after midnight count towards the strategies, not the ones before it nor in the next hour
+        let mut expected = vec![0; 60];
+        (0..10).for_each(|m| expected[m] = 1);
+        assert_eq!(expected, guards[0].alertness());
+        let summary = guards[0].summary();
+        assert_eq!((0, 1), (summary.worse_minute.minute, summary.worse_minute.sleeping));
+    }
+
+    #[test]
+    fn test_invalid_logs() {
+        fn check(log: &str) -> String {
+            parse(log).and_then(|events| to_shifts(&events)).unwrap_err()
+        }
+        assert_eq!("invalid event naps", check("[1518-02-15 00:10] naps"));
+        // events are sorted by timestamp first
+        assert_eq!("guard #1 is still asleep at the end of the log, since [1518-02-15 00:10]",
+                   check("[1518-02-15 00:10] falls asleep\n[1518-02-14 23:52] Guard #1 begins shift"));
+        assert_eq!("[1518-02-14 23:50] a guard falls asleep before the first shift",
+                   check("[1518-02-14 23:50] falls asleep\n[1518-02-14 23:52] Guard #1 begins shift"));
+        assert_eq!("[1518-02-15 00:20] guard #1 falls asleep but is already asleep since [1518-02-15 00:10]",
+                   check("[1518-02-14 23:52] Guard #1 begins shift\n[1518-02-15 00:10] falls asleep\n[1518-02-15 00:20] falls asleep"));
+        assert_eq!("[1518-02-15 00:10] guard #1 wakes up but isn't asleep",
+                   check("[1518-02-14 23:52] Guard #1 begins shift\n[1518-02-15 00:10] wakes up"));
+        assert_eq!("[1518-02-15 23:58] guard #2 begins shift while guard #1 is asleep since [1518-02-15 00:10]",
+                   check("[1518-02-14 23:52] Guard #1 begins shift\n[1518-02-15 00:10] falls asleep\n[1518-02-15 23:58] Guard #2 begins shift"));
+    }
+}
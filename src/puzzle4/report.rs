@@ -0,0 +1,190 @@
+use super::{Guard, Shift};
+use std::str::FromStr;
+
+#[derive(PartialEq, Debug)]
+pub(super) enum Format {
+    Text,
+    Csv
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "csv" => Ok(Format::Csv),
+            _ => Err(format!("unknown format {}, expected text or csv", s))
+        }
+    }
+}
+
+// How often the guard is asleep at their sleepiest minute, as a fraction of their shifts.
+//   A guard napping at the same time every night scores 1, one with no habits close to 0.
+fn consistency(guard: &Guard) -> f64 {
+    if guard.shifts.is_empty() {
+        return 0.0;
+    }
+    guard.summary().worse_minute.sleeping as f64 / guard.shifts.len() as f64
+}
+
+// Whether the guard was asleep at each minute of the midnight hour during the shift, the minutes the histograms count.
+fn midnight_hour(shift: &Shift) -> Vec<bool> {
+    let mut asleep = vec![false; 60];
+    for minute in shift.midnight_hour() {
+        asleep[minute as usize] = true;
+    }
+    asleep
+}
+
+fn sorted(guards: &[Guard]) -> Vec<&Guard> {
+    let mut guards = guards.iter().collect::<Vec<_>>();
+    guards.sort_by_key(|g| g.id);
+    guards
+}
+
+fn minutes_header() -> String {
+    (0 .. 60).map(|m| format!("{:02}", m)).collect::<Vec<_>>().join(",")
+}
+
+// Per guard statistics, the histogram counts how many shifts they were asleep at each minute of the midnight hour,
+//   as the chart shows them, while the total sleep also counts naps in other hours.
+pub(super) fn guards(guards: &[Guard], format: Format) -> String {
+    let mut lines = Vec::new();
+    if format == Format::Csv {
+        lines.push(format!("guard,shifts,total_sleep,sleepiest_minute,sleepiest_count,consistency,{}", minutes_header()));
+    }
+    for guard in sorted(guards) {
+        let summary = guard.summary();
+        let histogram = guard.alertness().iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        match format {
+            Format::Text => {
+                lines.push(format!("Guard #{}", guard.id));
+                lines.push(format!("  shifts: {}", guard.shifts.len()));
+                lines.push(format!("  total sleep: {} minutes", summary.total_sleep));
+                lines.push(format!("  sleepiest minute: {}, asleep on {} shifts", summary.worse_minute.minute, summary.worse_minute.sleeping));
+                lines.push(format!("  consistency: {:.0}%", consistency(guard) * 100.0));
+                lines.push(format!("  histogram: {}", histogram.join(" ")));
+            },
+            Format::Csv => lines.push(format!("{},{},{},{},{},{:.3},{}",
+                                              guard.id, guard.shifts.len(), summary.total_sleep, summary.worse_minute.minute,
+                                              summary.worse_minute.sleeping, consistency(guard), histogram.join(",")))
+        }
+    }
+    lines.join("\n")
+}
+
+// One row per shift, with the minutes of the midnight hour the guard was asleep (#) or awake (.)
+pub(super) fn chart(guards: &[Guard], format: Format) -> String {
+    let mut shifts = sorted(guards).into_iter()
+        .flat_map(|g| g.shifts.iter().map(move |s| (s, g.id)))
+        .collect::<Vec<_>>();
+    shifts.sort_by_key(|(s, id)| (s.date, *id));
+
+    let mut lines = Vec::new();
+    match format {
+        Format::Text => {
+            let width = shifts.iter().map(|(_, id)| id.to_string().len() + 1).max().unwrap_or(0).max(2);
+            let tens = (0 .. 60).map(|m| (m / 10).to_string()).collect::<String>();
+            let units = (0 .. 60).map(|m| (m % 10).to_string()).collect::<String>();
+            lines.push(format!("{:10}  {:width$}  Minute", "Date", "ID", width = width));
+            lines.push(format!("{:10}  {:width$}  {}", "", "", tens, width = width));
+            lines.push(format!("{:10}  {:width$}  {}", "", "", units, width = width));
+            for (shift, id) in shifts {
+                let minutes = midnight_hour(shift).iter().map(|asleep| if *asleep { '#' } else { '.' }).collect::<String>();
+                lines.push(format!("{}  {:width$}  {}", shift.date, format!("#{}", id), minutes, width = width));
+            }
+        },
+        Format::Csv => {
+            lines.push(format!("date,guard,{}", minutes_header()));
+            for (shift, id) in shifts {
+                let minutes = midnight_hour(shift).iter().map(|asleep| (*asleep as u8).to_string()).collect::<Vec<_>>();
+                lines.push(format!("{},{},{}", shift.date, id, minutes.join(",")));
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{parse, to_shifts};
+
+    const EXAMPLE: &str = r"[1518-11-01 00:00] Guard #10 begins shift
+[1518-11-01 00:05] falls asleep
+[1518-11-01 00:25] wakes up
+[1518-11-01 00:30] falls asleep
+[1518-11-01 00:55] wakes up
+[1518-11-01 23:58] Guard #99 begins shift
+[1518-11-02 00:40] falls asleep
+[1518-11-02 00:50] wakes up
+[1518-11-03 00:05] Guard #10 begins shift
+[1518-11-03 00:24] falls asleep
+[1518-11-03 00:29] wakes up
+[1518-11-04 00:02] Guard #99 begins shift
+[1518-11-04 00:36] falls asleep
+[1518-11-04 00:46] wakes up
+[1518-11-05 00:03] Guard #99 begins shift
+[1518-11-05 00:45] falls asleep
+[1518-11-05 00:55] wakes up";
+
+    fn example() -> Vec<Guard> {
+        to_shifts(&parse(EXAMPLE).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_chart() {
+        let expected = r"Date        ID   Minute
+                 000000000011111111112222222222333333333344444444445555555555
+                 012345678901234567890123456789012345678901234567890123456789
+1518-11-01  #10  .....####################.....#########################.....
+1518-11-02  #99  ........................................##########..........
+1518-11-03  #10  ........................#####...............................
+1518-11-04  #99  ....................................##########..............
+1518-11-05  #99  .............................................##########.....";
+        assert_eq!(expected, chart(&example(), Format::Text));
+
+        let csv = chart(&example(), Format::Csv);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(6, lines.len());
+        assert!(lines[0].starts_with("date,guard,00,01,02,"));
+        assert!(lines[2].starts_with("1518-11-02,99,0,0,0,"));
+    }
+
+    #[test]
+    fn test_naps_around_midnight() {
+        let night = to_shifts(&parse(r"[1518-11-01 23:40] Guard #7 begins shift
+[1518-11-01 23:50] falls asleep
+[1518-11-02 00:03] wakes up
+[1518-11-02 00:58] falls asleep
+[1518-11-02 01:02] wakes up").unwrap()).unwrap();
+        let chart = chart(&night, Format::Csv);
+        let charted = chart.lines().nth(1).unwrap().split(',').skip(2).collect::<Vec<_>>().join(",");
+        let report = guards(&night, Format::Csv);
+        let line = report.lines().nth(1).unwrap();
+        assert!(line.starts_with("7,1,17,0,1,1.000,"));
+        // the histogram of a single shift is its chart row
+        assert_eq!(charted, line.split(',').skip(6).collect::<Vec<_>>().join(","));
+    }
+
+    #[test]
+    fn test_guards() {
+        let text = guards(&example(), Format::Text);
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(vec![
+            "Guard #10",
+            "  shifts: 2",
+            "  total sleep: 50 minutes",
+            "  sleepiest minute: 24, asleep on 2 shifts",
+            "  consistency: 100%"
+        ], lines[0 .. 5].to_vec());
+        assert_eq!("  histogram: 0 0 0 0 0 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 2 1 1 1 1 0 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 0 0 0 0 0", lines[5]);
+        assert_eq!("  sleepiest minute: 45, asleep on 3 shifts", lines[9]);
+
+        let csv = guards(&example(), Format::Csv);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(3, lines.len());
+        assert!(lines[2].starts_with("99,3,30,45,3,1.000,0,0,"));
+    }
+}
@@ -1,42 +1,108 @@
 use bit_set::BitSet;
-use std::collections::HashSet;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::str::FromStr;
 
-fn collapse(input: &String) -> BitSet {
-    let chars = input.chars().collect::<Vec<_>>();
-    let mut collapsed = BitSet::with_capacity(input.len());
-    let mut left: usize = 0;
-    let mut right: usize = 1;
+mod rules;
+use rules::{Pairs, Polarity, Rule};
 
-    while right < input.len() {
-        let left_c = chars.get(left).unwrap();
-        let right_c = chars.get(right).unwrap();
+// Which units reacted, and in which order.
+#[derive(PartialEq, Debug)]
+struct Trace {
+    collapsed: BitSet,
+    // the positions of the units annihilating each other, as fed to the reactor
+    reactions: Vec<(usize, usize)>
+}
 
-        if left_c != right_c && left_c.to_ascii_lowercase() == right_c.to_ascii_lowercase() {
-            collapsed.insert(left);
-            collapsed.insert(right);
+// Reacts a polymer in a single pass, keeping the units that haven't reacted so far on a stack:
+//   each new unit either reacts with the one on top or is pushed on it.
+//   The units can be fed in as many chunks as needed, only the reacted polymer is kept in memory (and the trace if any).
+struct Reactor<R> {
+    rule: R,
+    stack: Vec<u8>,
+    fed: usize,
+    // when tracing, the positions of the units on the stack and the reactions so far
+    trace: Option<(Vec<usize>, Trace)>
+}
 
-            // backtrack until we find a non-collapsed char
-            while left > 0 && collapsed.contains(left) {
-                left = left - 1;
-            }
+impl<R: Rule> Reactor<R> {
+    fn new(rule: R) -> Self {
+        Reactor { rule, stack: Vec::new(), fed: 0, trace: None }
+    }
 
-            // if we backtracked to the start, we must instead move forward
-            if left == 0 && collapsed.contains(left) {
-                left = right + 1;
-                right = left + 1;
-            } else {
-                right = right + 1;
+    fn traced(rule: R) -> Self {
+        let trace = Trace { collapsed: BitSet::new(), reactions: Vec::new() };
+        Reactor { trace: Some((Vec::new(), trace)), ..Reactor::new(rule) }
+    }
+
+    // whitespace is skipped, e.g. the newline at the end of the input, but still counts towards the positions
+    fn feed<I: IntoIterator<Item = u8>>(&mut self, units: I) {
+        for unit in units {
+            let position = self.fed;
+            self.fed += 1;
+            if unit.is_ascii_whitespace() {
+                continue;
             }
-        } else {
-            left = left + 1;
-            while left < input.len() && collapsed.contains(left) {
-                left = left + 1;
+            match self.stack.last() {
+                Some(top) if self.rule.reacts(*top, unit) => {
+                    self.stack.pop();
+                    if let Some((positions, trace)) = &mut self.trace {
+                        let left = positions.pop().expect("untraced unit");
+                        trace.collapsed.insert(left);
+                        trace.collapsed.insert(position);
+                        trace.reactions.push((left, position));
+                    }
+                },
+                _ => {
+                    self.stack.push(unit);
+                    if let Some((positions, _)) = &mut self.trace {
+                        positions.push(position);
+                    }
+                }
             }
-            right = left + 1;
         }
     }
 
-    collapsed
+    fn polymer(self) -> Vec<u8> {
+        self.stack
+    }
+
+    fn trace(self) -> Option<Trace> {
+        self.trace.map(|(_, trace)| trace)
+    }
+}
+
+fn react<I: IntoIterator<Item = u8>, R: Rule>(units: I, rule: R) -> Vec<u8> {
+    let mut reactor = Reactor::new(rule);
+    reactor.feed(units);
+    reactor.polymer()
+}
+
+fn react_stream<T: Read, R: Rule>(mut reader: T, rule: R) -> io::Result<Vec<u8>> {
+    let mut reactor = Reactor::new(rule);
+    let mut chunk = vec![0; 1 << 16];
+    loop {
+        match reader.read(&mut chunk)? {
+            0 => break Ok(reactor.polymer()),
+            n => reactor.feed(chunk[.. n].iter().cloned())
+        }
+    }
+}
+
+fn collapse<R: Rule>(polymer: &[u8], rule: R) -> Trace {
+    let mut reactor = Reactor::traced(rule);
+    reactor.feed(polymer.iter().cloned());
+    reactor.trace().expect("untraced reactor")
+}
+
+fn describe(polymer: &[u8], trace: &Trace) -> String {
+    let mut lines = trace.reactions.iter().enumerate()
+        .map(|(step, (left, right))| format!("{}. {}-{} {}{}", step + 1, left, right, polymer[*left] as char, polymer[*right] as char))
+        .collect::<Vec<_>>();
+    lines.push(format!("{} of {} units reacted", trace.collapsed.len(), polymer.len()));
+    lines.join("\n")
 }
 
 pub fn mk(input: String) -> Box<dyn crate::Puzzle> {
@@ -48,33 +114,42 @@ pub struct Puzzle5 {
 
 impl crate::Puzzle for Puzzle5 {
     fn part1(&self) -> String {
-        let bits = collapse(&self.input);
-        let remains = self.input.len() - bits.len();
-        remains.to_string()
+        let polymer = react_stream(self.input.as_bytes(), Polarity).expect("cannot read polymer");
+        polymer.len().to_string()
     }
 
     fn part2(&self) -> String {
-        let mut all_units = HashSet::new();
-        for c in self.input.chars() {
-            all_units.insert(c.to_ascii_lowercase());
-        }
+        // removing a unit type and reacting gives the same result whether the polymer already reacted or not,
+        //   so we can start from the reacted polymer, which is much shorter.
+        let reacted = react(self.input.bytes(), Polarity);
+        let all_units = reacted.iter().map(|u| u.to_ascii_lowercase()).collect::<BTreeSet<_>>();
 
         let min_polymer = all_units.iter()
-            .map(|r| {
-                let mut copied = self.input.clone();
-                copied.retain(|c| c.to_ascii_lowercase() != *r);
-                let bits = collapse(&copied);
-                copied.len() - bits.len()
-            })
+            .map(|r| react(reacted.iter().cloned().filter(|u| u.to_ascii_lowercase() != *r), Polarity).len())
             .min();
 
-        min_polymer.expect("no polymer").to_string()
+        min_polymer.unwrap_or(reacted.len()).to_string()
+    }
+
+    fn query(&self, query: &str) -> Result<String, String> {
+        let pairs = |path: &str| {
+            let table = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+            Pairs::from_str(&table)
+        };
+        let polymer = self.input.as_bytes();
+        match query.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["react", path] => Ok(String::from_utf8_lossy(&react(polymer.iter().cloned(), pairs(path)?)).to_string()),
+            ["trace"] => Ok(describe(polymer, &collapse(polymer, Polarity))),
+            ["trace", path] => Ok(describe(polymer, &collapse(polymer, pairs(path)?))),
+            _ => Err(format!("unknown query {}, expected one of: react <pairs file>, trace [pairs file]", query))
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Puzzle;
     use std::ops::RangeInclusive;
 
     fn assert_collapsed(input: String, collapsed: Vec<RangeInclusive<usize>>) -> () {
@@ -84,7 +159,7 @@ mod tests {
                 bits.insert(b);
             }
         }
-        assert_eq!(collapse(&input), bits);
+        assert_eq!(collapse(input.as_bytes(), Polarity).collapsed, bits);
     }
 
     #[test]
@@ -105,4 +180,69 @@ mod tests {
         assert_collapsed("dabAcCaCBAcCcaDA".to_string(), vec![4..=5, 3..=6, 10..=11]);
         assert_collapsed("czYyZQMzZmSs".to_string(), vec![2..=3, 1..=4, 6..=11]);
     }
+
+    // removes the reacting pairs one at a time, stepping back after each removal in case the units around it react
+    fn naive_react(polymer: &str) -> Vec<u8> {
+        let mut units = polymer.trim().as_bytes().to_vec();
+        let mut i = 0;
+        while i + 1 < units.len() {
+            if units[i] != units[i + 1] && units[i].eq_ignore_ascii_case(&units[i + 1]) {
+                units.drain(i ..= i + 1);
+                i = i.saturating_sub(1);
+            } else {
+                i += 1;
+            }
+        }
+        units
+    }
+
+    #[test]
+    fn test_react() {
+        assert_eq!(b"dabCBAcaDA".to_vec(), react(b"dabAcCaCBAcCcaDA".iter().cloned(), Polarity));
+        assert_eq!(b"".to_vec(), react(b"cabBAC\n".iter().cloned(), Polarity));
+
+        let polymer = include_str!("input.txt");
+        let reacted = react(polymer.bytes(), Polarity);
+        let expected = naive_react(polymer);
+        assert_eq!(expected, reacted);
+        assert_eq!(expected.len(), polymer.trim().len() - collapse(polymer.as_bytes(), Polarity).collapsed.len());
+
+        // feeding the polymer in chunks that split reacting pairs
+        let mut reactor = Reactor::new(Polarity);
+        for chunk in polymer.as_bytes().chunks(7) {
+            reactor.feed(chunk.iter().cloned());
+        }
+        assert_eq!(reacted, reactor.polymer());
+        assert_eq!(reacted, react_stream(polymer.as_bytes(), Polarity).unwrap());
+    }
+
+    #[test]
+    fn test_rules() {
+        // digits annihilate when they add up to 10
+        let tens = |a: u8, b: u8| a.is_ascii_digit() && b.is_ascii_digit() && (a - b'0') + (b - b'0') == 10;
+        assert_eq!(b"3".to_vec(), react(b"1928553".iter().cloned(), tens));
+
+        let pairs = Pairs::from_str("ab cd").unwrap();
+        assert_eq!(b"aA".to_vec(), react(b"acdbaA".iter().cloned(), pairs));
+    }
+
+    #[test]
+    fn test_trace() {
+        let polymer = b"dabAcCaCBAcCcaDA";
+        let trace = collapse(polymer, Polarity);
+        assert_eq!(vec![(4, 5), (3, 6), (10, 11)], trace.reactions);
+        assert_eq!(r"1. 4-5 cC
+2. 3-6 Aa
+3. 10-11 cC
+6 of 16 units reacted", describe(polymer, &trace));
+
+        assert_eq!(None, Reactor::new(Polarity).trace());
+    }
+
+    #[test]
+    fn test_example() {
+        let puzzle = Puzzle5 { input: "dabAcCaCBAcCcaDA".to_string() };
+        assert_eq!("10", puzzle.part1());
+        assert_eq!("4", puzzle.part2());
+    }
 }
\ No newline at end of file
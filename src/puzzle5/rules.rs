@@ -0,0 +1,69 @@
+use std::str::FromStr;
+
+// Whether two adjacent units annihilate, `left` being the one closer to the start of the polymer.
+pub(super) trait Rule {
+    fn reacts(&self, left: u8, right: u8) -> bool;
+}
+
+// The same type of unit with opposite polarities, e.g. `aA` or `Bb`.
+pub(super) struct Polarity;
+
+impl Rule for Polarity {
+    fn reacts(&self, left: u8, right: u8) -> bool {
+        left != right && left.eq_ignore_ascii_case(&right)
+    }
+}
+
+impl<F: Fn(u8, u8) -> bool> Rule for F {
+    fn reacts(&self, left: u8, right: u8) -> bool {
+        self(left, right)
+    }
+}
+
+// The pairs of units annihilating each other, in either order. Any non whitespace byte can be a unit.
+//   Parsed from whitespace separated pairs, e.g. `aA bB xy`, lines starting with `#` are comments.
+pub(super) struct Pairs {
+    table: Vec<bool>
+}
+
+impl FromStr for Pairs {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut table = vec![false; 256 * 256];
+        let pairs = s.lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .flat_map(|line| line.split_whitespace());
+        for pair in pairs {
+            match pair.as_bytes() {
+                [a, b] => {
+                    table[*a as usize * 256 + *b as usize] = true;
+                    table[*b as usize * 256 + *a as usize] = true;
+                },
+                _ => return Err(format!("invalid pair {}, expected two single byte units", pair))
+            }
+        }
+        Ok(Pairs { table })
+    }
+}
+
+impl Rule for Pairs {
+    fn reacts(&self, left: u8, right: u8) -> bool {
+        self.table[left as usize * 256 + right as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pairs() {
+        let pairs = Pairs::from_str("# opposites attract\naB ba\n  xy\n").unwrap();
+        assert!(pairs.reacts(b'a', b'B') && pairs.reacts(b'B', b'a'));
+        assert!(pairs.reacts(b'y', b'x'));
+        assert!(!pairs.reacts(b'a', b'A'));
+        assert!(Pairs::from_str("aA b").is_err());
+        assert!(Pairs::from_str("abc").is_err());
+    }
+}
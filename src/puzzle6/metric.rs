@@ -0,0 +1,275 @@
+use super::{Area, Grid, Pt, Site};
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+// A fraction, to compare weighted distances exactly.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Ratio {
+    num: u64,
+    den: u64
+}
+
+impl Ratio {
+    pub(super) fn new(num: u64, den: u64) -> Ratio {
+        assert!(den > 0, "invalid ratio {}/{}", num, den);
+        Ratio { num, den }
+    }
+
+    pub(super) fn whole(num: u64) -> Ratio {
+        Ratio { num, den: 1 }
+    }
+}
+
+impl Ord for Ratio {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.num as u128 * other.den as u128).cmp(&(other.num as u128 * self.den as u128))
+    }
+}
+
+impl PartialOrd for Ratio {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Ratio {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ratio {}
+
+fn deltas(site: &Site, pt: Pt) -> (u64, u64) {
+    ((site.coord.left as i64 - pt.left as i64).abs() as u64, (site.coord.top as i64 - pt.top as i64).abs() as u64)
+}
+
+pub(super) trait Metric {
+    // Grows with the distance from the site to the cell, e.g. the square of the euclidean distance.
+    fn distance(&self, site: &Site, pt: Pt) -> Ratio;
+
+    // The power of the actual distance `distance` returns, to scale it consistently.
+    fn power(&self) -> u32 {
+        1
+    }
+
+    // The moves to the neighbouring cells, when distances are the number of moves along the shortest path.
+    //   The closest sites can then be found by flooding the area from all of them at once.
+    fn moves(&self) -> Option<&'static [(i32, i32)]> {
+        None
+    }
+
+    // Whether each site, indexed by id, has infinitely many closest cells, given whether their regions reach the border
+    //   of the sites' bounding box.
+    fn infinite(&self, sites: &[Site], on_border: &[bool]) -> Vec<bool>;
+}
+
+pub(super) struct Manhattan;
+
+impl Metric for Manhattan {
+    fn distance(&self, site: &Site, pt: Pt) -> Ratio {
+        let (left, top) = deltas(site, pt);
+        Ratio::whole(left + top)
+    }
+
+    fn moves(&self) -> Option<&'static [(i32, i32)]> {
+        Some(&[(-1, 0), (1, 0), (0, -1), (0, 1)])
+    }
+
+    // A region is infinite if and only if it reaches the border of the bounding box:
+    //   - moving away from the box from one of its border cells increases the distance to every site by one,
+    //     so the cells all the way to infinity have the same closest site.
+    //   - conversely, moving a cell outside of the box towards it decreases the distance to every site by one
+    //     until it reaches the border, so a region with cells outside of the box also has some on its border.
+    fn infinite(&self, _: &[Site], on_border: &[bool]) -> Vec<bool> {
+        on_border.to_vec()
+    }
+}
+
+pub(super) struct Chebyshev;
+
+impl Metric for Chebyshev {
+    fn distance(&self, site: &Site, pt: Pt) -> Ratio {
+        let (left, top) = deltas(site, pt);
+        Ratio::whole(left.max(top))
+    }
+
+    fn moves(&self) -> Option<&'static [(i32, i32)]> {
+        Some(&[(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (-1, 1), (1, -1), (1, 1)])
+    }
+
+    // Reaching the border says nothing here, but turned 45 degrees to u = x + y and v = x - y, chebyshev distances
+    //   are half the manhattan ones: the regions going on to infinity are the ones owning rays out of the box in u, v.
+    fn infinite(&self, sites: &[Site], _: &[bool]) -> Vec<bool> {
+        let turned = sites.iter()
+            .map(|site| (site.coord.left as i64 + site.coord.top as i64, site.coord.left as i64 - site.coord.top as i64))
+            .collect::<Vec<_>>();
+        manhattan_rays(&turned)
+    }
+}
+
+// Whether each site has the closest cells all along some ray out of the bounding box, with manhattan distances.
+//   Past the right side, the distance from a cell at (u, v) to a site at (su, sv) is u - su + |v - sv| and
+//   the closest site along the ray doesn't change with u. Beyond the corners, it's the one for the closest v on the side.
+//   Every cell outside of the box is on one of those rays, the ones inside are finitely many.
+fn manhattan_rays(sites: &[(i64, i64)]) -> Vec<bool> {
+    let mut infinite = vec![false; sites.len()];
+    let (us, vs) = (sites.iter().map(|s| s.0), sites.iter().map(|s| s.1));
+    let (u_range, v_range) = (us.clone().min().unwrap_or(0) ..= us.max().unwrap_or(-1), vs.clone().min().unwrap_or(0) ..= vs.max().unwrap_or(-1));
+    // the site with the unique smallest distance offset along each ray, if any
+    let mut closest = |offset: &dyn Fn(&(i64, i64)) -> i64| {
+        let offsets = sites.iter().map(offset).collect::<Vec<_>>();
+        let min = offsets.iter().min().cloned();
+        let mut closest = (0 .. sites.len()).filter(|idx| Some(offsets[*idx]) == min);
+        if let (Some(idx), None) = (closest.next(), closest.next()) {
+            infinite[idx] = true;
+        }
+    };
+    for v in v_range {
+        closest(&|(su, sv)| (v - sv).abs() - su);
+        closest(&|(su, sv)| (v - sv).abs() + su);
+    }
+    for u in u_range {
+        closest(&|(su, sv)| (u - su).abs() - sv);
+        closest(&|(su, sv)| (u - su).abs() + sv);
+    }
+    infinite
+}
+
+pub(super) struct Euclidean;
+
+impl Metric for Euclidean {
+    // squared, to stay exact
+    fn distance(&self, site: &Site, pt: Pt) -> Ratio {
+        let (left, top) = deltas(site, pt);
+        Ratio::whole(left * left + top * top)
+    }
+
+    fn power(&self) -> u32 {
+        2
+    }
+
+    // A region is infinite if and only if its site is on the boundary of the sites' convex hull, and alone at its coordinates:
+    //   - moving away from such a site, perpendicularly to a hull edge through it, gets strictly closer to it than
+    //     to any other site, whether on the edge's line or inside the hull, and those cells are all the way to infinity.
+    //   - a site strictly inside the hull has other sites all around, its region is bounded by their bisectors.
+    //   - sites at the same coordinates tie on every cell.
+    fn infinite(&self, sites: &[Site], _: &[bool]) -> Vec<bool> {
+        on_hull(sites)
+    }
+}
+
+// Whether each site is on the boundary of the convex hull of all sites, without any other site at its coordinates.
+fn on_hull(sites: &[Site]) -> Vec<bool> {
+    let xy = |pt: Pt| (pt.left as i64, pt.top as i64);
+    let mut pts = sites.iter().map(|site| xy(site.coord)).collect::<Vec<_>>();
+    pts.sort();
+    let shared = pts.windows(2).filter(|w| w[0] == w[1]).map(|w| w[0]).collect::<Vec<_>>();
+    pts.dedup();
+
+    // the hull's vertices, going around counterclockwise (with y going down) by monotone chains,
+    //   dropping the points in the middle of edges
+    let cross = |o: (i64, i64), a: (i64, i64), b: (i64, i64)| (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0);
+    let mut hull: Vec<(i64, i64)> = Vec::new();
+    for pt in pts.iter() {
+        while hull.len() >= 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], *pt) <= 0 {
+            hull.pop();
+        }
+        hull.push(*pt);
+    }
+    let lower = hull.len() + 1;
+    for pt in pts.iter().rev().skip(1) {
+        while hull.len() >= lower && cross(hull[hull.len() - 2], hull[hull.len() - 1], *pt) <= 0 {
+            hull.pop();
+        }
+        hull.push(*pt);
+    }
+    hull.pop();
+
+    let on_edge = |pt: (i64, i64), a: (i64, i64), b: (i64, i64)| {
+        cross(a, b, pt) == 0 && a.0.min(b.0) <= pt.0 && pt.0 <= a.0.max(b.0) && a.1.min(b.1) <= pt.1 && pt.1 <= a.1.max(b.1)
+    };
+    sites.iter()
+        .map(|site| {
+            let pt = xy(site.coord);
+            // with all sites on a line, the hull is flat and they're all on its boundary
+            !shared.contains(&pt) && (hull.len() <= 2 || (0 .. hull.len()).any(|i| on_edge(pt, hull[i], hull[(i + 1) % hull.len()])))
+        })
+        .collect()
+}
+
+// Multiplicatively weighted: the distance to each site is divided by its weight, so heavier sites reach further.
+pub(super) struct Weighted<M> {
+    metric: M,
+    // indexed by site id
+    weights: Vec<u64>
+}
+
+impl<M: Metric> Weighted<M> {
+    pub(super) fn new(metric: M, weights: Vec<u64>) -> Result<Self, String> {
+        if weights.contains(&0) {
+            return Err("weights must be positive".to_string());
+        }
+        // so that squared weights still fit the ratios' denominators
+        if let Some(weight) = weights.iter().find(|w| **w > u32::MAX as u64) {
+            return Err(format!("weights must be at most {}, got {}", u32::MAX, weight));
+        }
+        Ok(Weighted { metric, weights })
+    }
+}
+
+impl<M: Metric> Metric for Weighted<M> {
+    fn distance(&self, site: &Site, pt: Pt) -> Ratio {
+        let Ratio { num, den } = self.metric.distance(site, pt);
+        let weight = self.weights.get(site.id).unwrap_or_else(|| panic!("missing weight for site {}", site.id));
+        Ratio::new(num, den * weight.pow(self.metric.power()))
+    }
+
+    fn power(&self) -> u32 {
+        self.metric.power()
+    }
+
+    // Far enough from all sites, the lighter ones are never the closest: the regions going on to infinity are
+    //   the ones the heaviest sites have among themselves, with the unweighted metric.
+    fn infinite(&self, sites: &[Site], on_border: &[bool]) -> Vec<bool> {
+        let weight = |site: &Site| *self.weights.get(site.id).unwrap_or_else(|| panic!("missing weight for site {}", site.id));
+        let heaviest = sites.iter().map(weight).max().unwrap_or(0);
+        let heavy = sites.iter().filter(|site| weight(site) == heaviest).collect::<Vec<_>>();
+        if heavy.len() == sites.len() {
+            return self.metric.infinite(sites, on_border);
+        }
+
+        // the heaviest sites are renumbered in the area they make up on their own
+        let area = Area::new(&heavy.iter().map(|site| site.coord).collect::<Vec<_>>());
+        let on_border = Grid::new(&area, &self.metric).on_border(area.sites.len());
+        let mut infinite = vec![false; sites.len()];
+        for (site, heavy_infinite) in heavy.iter().zip(self.metric.infinite(&area.sites, &on_border)) {
+            infinite[site.id] = heavy_infinite;
+        }
+        infinite
+    }
+}
+
+// Parses `manhattan`, `chebyshev`, `euclidean` or a weighted one like `weighted euclidean 1,3,2`, with a weight per site.
+pub(super) fn parse(s: &str, sites: usize) -> Result<Box<dyn Metric>, String> {
+    match s.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["manhattan"] => Ok(Box::new(Manhattan)),
+        ["chebyshev"] => Ok(Box::new(Chebyshev)),
+        ["euclidean"] => Ok(Box::new(Euclidean)),
+        ["weighted", name, weights] => {
+            let weights = weights.split(',')
+                .map(|w| u64::from_str(w).map_err(|_| format!("invalid weight {}", w)))
+                .collect::<Result<Vec<_>, _>>()?;
+            if weights.len() != sites {
+                return Err(format!("expected {} weights, one per site, but got {}", sites, weights.len()));
+            }
+            match *name {
+                "manhattan" => Ok(Box::new(Weighted::new(Manhattan, weights)?)),
+                "chebyshev" => Ok(Box::new(Weighted::new(Chebyshev, weights)?)),
+                "euclidean" => Ok(Box::new(Weighted::new(Euclidean, weights)?)),
+                _ => Err(format!("unknown metric {}, expected manhattan, chebyshev or euclidean", name))
+            }
+        },
+        _ => Err(format!("unknown metric {}, expected manhattan, chebyshev, euclidean or weighted <metric> <weights>", s))
+    }
+}
@@ -1,10 +1,13 @@
 use std::str::FromStr;
-use std::cmp::{min, max, Ordering};
-use std::collections::HashMap;
+use std::cmp::{min, max};
+use std::collections::VecDeque;
 use std::fmt::Display;
 use termion::color;
 
-#[derive(PartialEq, Eq, Ord, Hash, Debug, Copy, Clone)]
+mod metric;
+use metric::{Manhattan, Metric};
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Copy, Clone)]
 struct Pt {
     left: u16,
     top: u16
@@ -22,18 +25,6 @@ impl Pt {
     }
 }
 
-impl PartialOrd for Pt {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let origin = &Pt::new(0,0);
-        let self_dist = self.distance(origin);
-        let other_dist = other.distance(origin);
-
-        if self_dist < other_dist { Some(Ordering::Less) }
-        else if other_dist < self_dist { Some(Ordering::Greater) }
-        else { Some(Ordering::Equal) }
-    }
-}
-
 impl FromStr for Pt {
     type Err = std::num::ParseIntError;
 
@@ -47,25 +38,31 @@ impl FromStr for Pt {
     }
 }
 
+// Labels only serve to render the area, they repeat after 52 sites.
+fn label(id: usize) -> char {
+    const LABELS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+    LABELS[id % LABELS.len()] as char
+}
+
 #[derive(PartialEq, Eq, Hash, Debug, Clone)]
-struct Named {
-    name: char,
+struct Site {
+    // index of the site in the area
+    id: usize,
+    label: char,
     coord: Pt
 }
 
-
 #[derive(PartialEq, Eq, Debug, Clone)]
 struct Area {
     top_left: Pt,
     bottom_right: Pt,
-    names: Vec<Named>
+    sites: Vec<Site>
 }
 
 impl Area {
 
-    fn new(pts: &Vec<Pt>) -> Area {
-        let mut names = Vec::new();
-        let mut name = 'A';
+    fn new(pts: &[Pt]) -> Area {
+        assert!(!pts.is_empty(), "an area needs at least one site");
         let mut top_left = Pt::max();
         let mut bottom_right = Pt::min();
 
@@ -74,106 +71,252 @@ impl Area {
             top_left.left = min(top_left.left, pt.left);
             bottom_right.top = max(bottom_right.top, pt.top);
             bottom_right.left = max(bottom_right.left, pt.left);
-            names.push(Named { name, coord: *pt });
-            name = (name as u8 + 1 as u8) as char;
         });
+        let sites = pts.iter().enumerate()
+            .map(|(id, pt)| Site { id, label: label(id), coord: *pt })
+            .collect();
 
-        Area { top_left, bottom_right, names }
-    }
-
-    fn analyze(&self, max_distance: u32) -> AreaAnalysis {
-        let mut name_analysis= HashMap::new();
-        for n in self.names.iter() {
-            name_analysis.insert(n, NamedAnalysis { infinite: false, area: 0 });
-        }
-        let mut pt_analysis = HashMap::new();
-        for top in self.top_left.top..=self.bottom_right.top {
-            for left in self.top_left.left..=self.bottom_right.left {
-                let coord = Pt { left, top };
-                let is_frontier = top == self.top_left.top || top == self.bottom_right.top || left == self.bottom_right.left || left == self.top_left.left;
-                let mut distances = HashMap::new();
-                let mut shortest = std::u16::MAX;
-                self.names.iter().for_each(|x| {
-                    let distance = x.coord.distance(&coord);
-                    distances.insert(x, distance);
-                    shortest = min(shortest, distance);
-                });
-                let dominating = distances.iter().filter_map(|(name, dist)| {
-                    if *dist == shortest { Some(*name) } else { None }
-                }).collect::<Vec<_>>();
-
-                let dominated = if dominating.len() != 1 { None } else {
-                    let dom = *dominating.first().expect("cannot happen");
-                    let a = name_analysis.get(dom).expect("cannot happen");
-                    let na = NamedAnalysis { infinite: a.infinite || is_frontier, area: a.area + 1 };
-                    name_analysis.insert(dom, na);
-                    Some(dom)
-                };
-
-                let dist_sum: u32 = distances.values().map(|x| *x as u32).sum();
-                let is_part2_region = dist_sum < max_distance;
-
-                pt_analysis.insert(coord, PtAnalysis { is_frontier, is_part2_region, distances, dominated });
+        Area { top_left, bottom_right, sites }
+    }
+
+    // The part 2 region always uses manhattan distances, as in the puzzle.
+    fn analyze<M: Metric + ?Sized>(&self, metric: &M, max_distance: u32) -> AreaAnalysis<'_> {
+        let grid = Grid::new(self, metric);
+
+        let mut regions = grid.on_border(self.sites.len()).into_iter()
+            .map(|on_border| Region { on_border, area: 0 })
+            .collect::<Vec<_>>();
+        for owner in grid.owners.iter() {
+            if let Owner::Site(id) = owner {
+                regions[*id].area += 1;
             }
         }
-        AreaAnalysis { area: self, pt_analysis, name_analysis }
+
+        // the sum of the distances to all sites is the sum of the distances along each axis
+        let lefts = distance_sums(self.sites.iter().map(|s| s.coord.left as i64), self.top_left.left as i64, self.bottom_right.left as i64);
+        let tops = distance_sums(self.sites.iter().map(|s| s.coord.top as i64), self.top_left.top as i64, self.bottom_right.top as i64);
+        let part2_region = (0 .. grid.owners.len())
+            .map(|idx| lefts[idx % grid.width] + tops[idx / grid.width] < max_distance as u64)
+            .collect();
+
+        AreaAnalysis { area: self, grid, regions, part2_region }
     }
 }
 
-#[derive(Debug)]
-struct NamedAnalysis {
-    infinite: bool,
-    area: u32
+// The number of cells anywhere, not only in the bounding box, whose total distance to all sites is under max_distance.
+//   The distances add up along each axis separately: the total for a cell is the sum of its column's and its row's.
+//   Outside of the bounding box, the column and row sums grow by the number of sites with each step further,
+//   so they are all over max_distance after max_distance / sites steps.
+fn safe_region_size(sites: &[Site], max_distance: u32) -> u64 {
+    let max_distance = max_distance as u64;
+    let margin = (max_distance / sites.len() as u64) as i64 + 1;
+    let sorted_sums = |coords: Vec<i64>| {
+        let (from, to) = (*coords.iter().min().expect("no sites"), *coords.iter().max().expect("no sites"));
+        let mut sums = distance_sums(coords.into_iter(), from - margin, to + margin);
+        sums.sort();
+        sums
+    };
+    let lefts = sorted_sums(sites.iter().map(|s| s.coord.left as i64).collect());
+    let tops = sorted_sums(sites.iter().map(|s| s.coord.top as i64).collect());
+
+    // the larger the column's sum, the fewer rows are close enough
+    let mut rows = tops.len();
+    lefts.iter()
+        .map(|left| {
+            while rows > 0 && left + tops[rows - 1] >= max_distance {
+                rows -= 1;
+            }
+            rows as u64
+        })
+        .sum()
+}
+
+// For each position from `from` to `to` included, the sum of its distances to the given coordinates along one axis.
+fn distance_sums<I: Iterator<Item = i64>>(coords: I, from: i64, to: i64) -> Vec<u64> {
+    let mut coords = coords.collect::<Vec<_>>();
+    coords.sort();
+    let mut sum = coords.iter().map(|c| (c - from).abs() as u64).sum::<u64>();
+    let mut sums = Vec::with_capacity((to - from) as usize + 1);
+    let mut before = coords.iter().take_while(|c| **c <= from).count();
+    for pos in from ..= to {
+        sums.push(sum);
+        while before < coords.len() && coords[before] <= pos {
+            before += 1;
+        }
+        // moving one step further from the coordinates before (or at) pos, closer to the ones after
+        sum = sum + before as u64 - (coords.len() - before) as u64;
+    }
+    sums
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum Owner {
+    Site(usize),
+    // several sites are the closest
+    Tie
 }
 
-struct PtAnalysis<'a> {
-    is_frontier: bool,
-    is_part2_region: bool,
-    distances: HashMap<&'a Named, u16>,
-    dominated: Option<&'a Named> // Some when the named origin dominates this coordinate
+// The closest site of each cell of the area's bounding box, row by row.
+struct Grid {
+    width: usize,
+    height: usize,
+    owners: Vec<Owner>,
+    // whether there's a site on each cell
+    sites: Vec<bool>
 }
+
+impl Grid {
+    fn new<M: Metric + ?Sized>(area: &Area, metric: &M) -> Grid {
+        let width = (area.bottom_right.left - area.top_left.left) as usize + 1;
+        let height = (area.bottom_right.top - area.top_left.top) as usize + 1;
+        let mut sites = vec![false; width * height];
+        for site in area.sites.iter() {
+            sites[(site.coord.top - area.top_left.top) as usize * width + (site.coord.left - area.top_left.left) as usize] = true;
+        }
+        let owners = match metric.moves() {
+            Some(moves) => Grid::flood(area, width, height, moves),
+            None => Grid::compare(area, width, height, metric)
+        };
+
+        Grid { width, height, owners, sites }
+    }
+
+    // Floods the bounding box from all sites at once, breadth first, in O(cells).
+    //   Each cell is reached at its distance to the closest sites, from neighbours one move closer to the same sites:
+    //   it's owned by a single site when all of them are, else it's a tie.
+    //   Shortest paths between two cells stay within their bounding box, so the flood doesn't need to go outside of it.
+    fn flood(area: &Area, width: usize, height: usize, moves: &[(i32, i32)]) -> Vec<Owner> {
+        let mut distances = vec![std::u32::MAX; width * height];
+        let mut owners = vec![Owner::Tie; width * height];
+
+        let mut queue = VecDeque::new();
+        for site in area.sites.iter() {
+            let idx = (site.coord.top - area.top_left.top) as usize * width + (site.coord.left - area.top_left.left) as usize;
+            if distances[idx] == 0 {
+                // two sites at the same coordinates
+                owners[idx] = Owner::Tie;
+            } else {
+                distances[idx] = 0;
+                owners[idx] = Owner::Site(site.id);
+                queue.push_back(idx);
+            }
+        }
+
+        while let Some(idx) = queue.pop_front() {
+            let (left, top) = ((idx % width) as i32, (idx / width) as i32);
+            let distance = distances[idx] + 1;
+            for (move_left, move_top) in moves {
+                let (left, top) = (left + move_left, top + move_top);
+                if left < 0 || top < 0 || left >= width as i32 || top >= height as i32 {
+                    continue;
+                }
+                let next = top as usize * width + left as usize;
+                if distances[next] == std::u32::MAX {
+                    distances[next] = distance;
+                    owners[next] = owners[idx];
+                    queue.push_back(next);
+                } else if distances[next] == distance && owners[next] != owners[idx] {
+                    owners[next] = Owner::Tie;
+                }
+            }
+        }
+        owners
+    }
+
+    // Compares the distances to all sites for every cell, in O(cells * sites).
+    fn compare<M: Metric + ?Sized>(area: &Area, width: usize, height: usize, metric: &M) -> Vec<Owner> {
+        (0 .. width * height)
+            .map(|idx| {
+                let pt = Pt::new(area.top_left.left + (idx % width) as u16, area.top_left.top + (idx / width) as u16);
+                let mut closest = None;
+                let mut owner = Owner::Tie;
+                for site in area.sites.iter() {
+                    let distance = metric.distance(site, pt);
+                    match closest {
+                        Some(c) if distance > c => (),
+                        Some(c) if distance == c => owner = Owner::Tie,
+                        _ => {
+                            closest = Some(distance);
+                            owner = Owner::Site(site.id);
+                        }
+                    }
+                }
+                owner
+            })
+            .collect()
+    }
+
+    fn is_frontier(&self, idx: usize) -> bool {
+        let (left, top) = (idx % self.width, idx / self.width);
+        left == 0 || top == 0 || left + 1 == self.width || top + 1 == self.height
+    }
+
+    // Whether the region of each site reaches the border of the bounding box, indexed by site id.
+    fn on_border(&self, sites: usize) -> Vec<bool> {
+        let mut on_border = vec![false; sites];
+        for (idx, owner) in self.owners.iter().enumerate() {
+            if let Owner::Site(id) = owner {
+                on_border[*id] |= self.is_frontier(idx);
+            }
+        }
+        on_border
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Region {
+    // whether some of its cells are on the border of the bounding box, the metric tells whether it goes on to infinity
+    on_border: bool,
+    area: u32
+}
+
 struct AreaAnalysis<'a> {
     area: &'a Area,
-    pt_analysis: HashMap<Pt, PtAnalysis<'a>>,
-    name_analysis: HashMap<&'a Named, NamedAnalysis>,
+    grid: Grid,
+    // indexed by site id
+    regions: Vec<Region>,
+    // whether each cell of the grid is within the part 2 region
+    part2_region: Vec<bool>,
+}
+
+impl AreaAnalysis<'_> {
+    // The area of each region, indexed by site id, or None for the ones going on to infinity.
+    fn finite_areas<M: Metric + ?Sized>(&self, metric: &M) -> Vec<Option<u32>> {
+        let on_border = self.regions.iter().map(|region| region.on_border).collect::<Vec<_>>();
+        let infinite = metric.infinite(&self.area.sites, &on_border);
+        self.regions.iter().zip(infinite).map(|(region, infinite)| if infinite { None } else { Some(region.area) }).collect()
+    }
 }
 
 impl Display for AreaAnalysis<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        for top in self.area.top_left.top..=self.area.bottom_right.top {
-            for left in self.area.top_left.left..=self.area.bottom_right.left {
-                let pt = Pt { left, top };
-                let analysis = self.pt_analysis.get(&pt).expect(&format!("missing pt {:?} in analysis", pt).to_owned());
-
-                let is_name = analysis.dominated.map(|x| {
-                    let dist = *analysis.distances.get(x).expect("invalid area");
-                    dist == 0
-                }).unwrap_or(false);
-
-                let symbol = match (analysis.dominated, analysis.is_part2_region) {
-                    (None, true) =>'#',
-                    (None, false) => '.',
-                    (Some(p), true) => if is_name { p.name } else { '#' },
-                    (Some(p), _) => p.name
-                };
-
-                match (analysis.is_frontier, is_name) {
-                    (false, false) => write!(f, "{}{}", color::Fg(color::Cyan), symbol)?,
-                    (false, true) => write!(f, "{}{}", color::Fg(color::LightCyan), symbol)?,
-                    (true, false) => write!(f, "{}{}", color::Fg(color::Red), symbol)?,
-                    (true, true) => write!(f, "{}{}", color::Fg(color::LightRed), symbol)?,
-                }
+        for (idx, owner) in self.grid.owners.iter().enumerate() {
+            let is_name = self.grid.sites[idx];
+
+            let symbol = match (owner, self.part2_region[idx]) {
+                (Owner::Tie, true) =>'#',
+                (Owner::Tie, false) => '.',
+                (Owner::Site(id), true) => if is_name { self.area.sites[*id].label } else { '#' },
+                (Owner::Site(id), _) => self.area.sites[*id].label
+            };
+
+            match (self.grid.is_frontier(idx), is_name) {
+                (false, false) => write!(f, "{}{}", color::Fg(color::Cyan), symbol)?,
+                (false, true) => write!(f, "{}{}", color::Fg(color::LightCyan), symbol)?,
+                (true, false) => write!(f, "{}{}", color::Fg(color::Red), symbol)?,
+                (true, true) => write!(f, "{}{}", color::Fg(color::LightRed), symbol)?,
+            }
+            if (idx + 1) % self.grid.width == 0 {
+                write!(f, "\n{}", color::Fg(color::Reset))?;
             }
-            write!(f, "\n{}", color::Fg(color::Reset))?;
         }
         Ok(())
     }
 }
 
+// The sites keep the order of the input, which the weights of weighted metrics follow.
 fn parse(input: String) -> Vec<Pt> {
-    let mut pts = input.lines().map(|x| Pt::from_str(x).expect("invalid input")).collect::<Vec<_>>();
-    pts.sort();
-    pts
+    input.lines().map(|x| Pt::from_str(x).expect("invalid input")).collect()
 }
 
 pub fn mk(input: String) -> Box<dyn crate::Puzzle> {
@@ -187,35 +330,44 @@ struct Puzzle6 {
 impl crate::Puzzle for Puzzle6 {
     fn part1(&self) -> String {
         let area = Area::new(&self.coords);
-        let analysis = area.analyze(10000);
-
-        println!("{}", analysis);
-        let mut finite_areas = analysis.name_analysis.iter()
-            .filter(|(_, a)| !a.infinite)
-            .collect::<Vec<_>>();
-
-        finite_areas.sort_by_key(|(_, a)| 0-a.area as i32);
-
-
-        finite_areas.iter().for_each(|(name, analysis)| {
-            println!("{:?} -> {:?}", name, analysis)
-        });
-
-        let (_, largest) = finite_areas.first().expect("invalid solution");
-        format!("{}", largest.area)
+        let analysis = area.analyze(&Manhattan, 10000);
+        let largest = analysis.finite_areas(&Manhattan)
+            .into_iter()
+            .flatten()
+            .max()
+            .expect("invalid solution");
+        format!("{}", largest)
     }
 
     fn part2(&self) -> String {
         let area = Area::new(&self.coords);
-        let analysis = area.analyze(10000);
 
-        analysis.pt_analysis.values().filter(|x| x.is_part2_region).count().to_string()
+        safe_region_size(&area.sites, 10000).to_string()
+    }
+
+    fn query(&self, query: &str) -> Result<String, String> {
+        let area = Area::new(&self.coords);
+        let mut parts = query.splitn(2, ' ');
+        match (parts.next(), parts.next()) {
+            (Some("largest"), Some(metric)) => {
+                let metric = metric::parse(metric, area.sites.len())?;
+                let analysis = area.analyze(&*metric, 0);
+                area.sites.iter().zip(analysis.finite_areas(&*metric))
+                    .filter_map(|(site, size)| size.map(|size| (site, size)))
+                    .max_by_key(|(site, size)| (*size, std::cmp::Reverse(site.id)))
+                    .map(|(site, size)| format!("{} at {},{}: {}", site.label, site.coord.left, site.coord.top, size))
+                    .ok_or_else(|| "all regions are infinite".to_string())
+            },
+            (Some("render"), Some(metric)) => Ok(area.analyze(&*metric::parse(metric, area.sites.len())?, 10000).to_string()),
+            _ => Err(format!("unknown query {}, expected one of: largest <metric>, render <metric>", query))
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::Puzzle;
 
     #[test]
     fn test_distance() {
@@ -232,6 +384,210 @@ mod test {
 //        assert_eq!(*Area::new(&Pt { left: 1, top: 1 }).add(&Pt { left: 3, top: 3}).add(&Pt { left: 2, top: 4}), Area { top_left: Pt { left: 1, top: 1}, bottom_right: Pt { left: 3, top: 4} });
 
         let area = Area::new(&vec![Pt::new(1,1), Pt::new(1,6), Pt::new(8,3), Pt::new(3,4), Pt::new(5,5), Pt::new(8,9)] );
-        println!("{}", area.analyze(32));
+        let analysis = area.analyze(&Manhattan, 32);
+        println!("{}", analysis);
+
+        assert_eq!(vec![None, None, None, Some(9), Some(17), None], analysis.finite_areas(&Manhattan));
+        assert_eq!(16, analysis.part2_region.iter().filter(|x| **x).count());
+    }
+
+    #[test]
+    fn test_input_order() {
+        let pts = parse("5, 5\n1, 1\n".to_string());
+        assert_eq!(vec![Pt::new(5,5), Pt::new(1,1)], pts);
+
+        // 1,1 is the heavier site: 4,1 is at 3/3 from it, and at 5/1 from 5,5
+        let area = Area::new(&pts);
+        let grid = Grid::new(&area, &*metric::parse("weighted manhattan 1,3", 2).unwrap());
+        match grid.owners[3] {
+            Owner::Site(id) => assert_eq!(Pt::new(1,1), area.sites[id].coord),
+            Owner::Tie => panic!("4,1 has a single closest site")
+        }
     }
-}
\ No newline at end of file
+
+    // the closest sites and distances sums of every cell, checking all sites
+    fn assert_matches_brute_force(area: &Area, max_distance: u32) {
+        let analysis = area.analyze(&Manhattan, max_distance);
+        for (idx, owner) in analysis.grid.owners.iter().enumerate() {
+            let pt = Pt::new(area.top_left.left + (idx % analysis.grid.width) as u16, area.top_left.top + (idx / analysis.grid.width) as u16);
+            let distances = area.sites.iter().map(|s| s.coord.distance(&pt) as u32).collect::<Vec<_>>();
+            let closest = *distances.iter().min().unwrap();
+            let closest_sites = area.sites.iter().filter(|s| distances[s.id] == closest).collect::<Vec<_>>();
+            let expected = if closest_sites.len() == 1 { Owner::Site(closest_sites[0].id) } else { Owner::Tie };
+            assert_eq!(expected, *owner, "at {:?}", pt);
+            assert_eq!(distances.iter().sum::<u32>() < max_distance, analysis.part2_region[idx], "at {:?}", pt);
+        }
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        assert_matches_brute_force(&Area::new(&parse(include_str!("input.txt").to_string())), 10000);
+
+        // many sites, some of them at the same coordinates
+        let pts = (0 .. 3000u32).map(|i| Pt::new((i * 7919 % 311) as u16, (i * 104_729 % 293) as u16)).collect::<Vec<_>>();
+        assert_matches_brute_force(&Area::new(&pts), 300_000);
+    }
+
+    // the closest site of a cell anywhere on the plane, if there is a single one
+    fn closest(area: &Area, left: i64, top: i64) -> Option<usize> {
+        let distances = area.sites.iter()
+            .map(|s| (s.coord.left as i64 - left).abs() + (s.coord.top as i64 - top).abs())
+            .collect::<Vec<_>>();
+        let closest = *distances.iter().min().unwrap();
+        let mut closest_sites = area.sites.iter().filter(|s| distances[s.id] == closest);
+        match (closest_sites.next(), closest_sites.next()) {
+            (Some(site), None) => Some(site.id),
+            _ => None
+        }
+    }
+
+    #[test]
+    fn test_infinite_regions() {
+        let mut inputs = vec![parse(include_str!("input.txt").to_string())];
+        inputs.push(vec![Pt::new(1,1), Pt::new(1,6), Pt::new(8,3), Pt::new(3,4), Pt::new(5,5), Pt::new(8,9)]);
+        // a site surrounded on all sides, and the ones around it reaching the border through ties
+        inputs.push(vec![Pt::new(5,5), Pt::new(5,0), Pt::new(0,5), Pt::new(10,5), Pt::new(5,10), Pt::new(0,0), Pt::new(10,10)]);
+
+        for pts in inputs {
+            let area = Area::new(&pts);
+            let analysis = area.analyze(&Manhattan, 0);
+            // the regions reaching the border of a much larger window go on to infinity
+            let margin = 100;
+            let (left, top) = (area.top_left.left as i64 - margin, area.top_left.top as i64 - margin);
+            let (right, bottom) = (area.bottom_right.left as i64 + margin, area.bottom_right.top as i64 + margin);
+            let mut infinite = vec![false; area.sites.len()];
+            let border = (left ..= right).flat_map(|x| vec![(x, top), (x, bottom)])
+                .chain((top ..= bottom).flat_map(|y| vec![(left, y), (right, y)]));
+            for (x, y) in border {
+                if let Some(id) = closest(&area, x, y) {
+                    infinite[id] = true;
+                }
+            }
+            assert_eq!(infinite, analysis.finite_areas(&Manhattan).iter().map(|a| a.is_none()).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_safe_region() {
+        let area = Area::new(&[Pt::new(1,1), Pt::new(1,6), Pt::new(8,3), Pt::new(3,4), Pt::new(5,5), Pt::new(8,9)]);
+        assert_eq!(16, safe_region_size(&area.sites, 32));
+
+        // large thresholds reach way past the bounding box, which only has 80 cells
+        for max_distance in vec![0, 1, 31, 32, 60, 100, 250] {
+            let mut expected = 0;
+            for x in -100 ..= 110 {
+                for y in -100 ..= 110 {
+                    let total: i64 = area.sites.iter().map(|s| (s.coord.left as i64 - x).abs() + (s.coord.top as i64 - y).abs()).sum();
+                    if total < max_distance {
+                        expected += 1;
+                    }
+                }
+            }
+            assert_eq!(expected, safe_region_size(&area.sites, max_distance as u32), "max distance {}", max_distance);
+        }
+        let analysis = area.analyze(&Manhattan, 100);
+        assert!(analysis.part2_region.iter().all(|x| *x));
+        assert!(safe_region_size(&area.sites, 100) > analysis.part2_region.len() as u64);
+    }
+
+    // the same metric, without the shortcut of flooding the area
+    struct Compared<M>(M);
+
+    impl<M: Metric> Metric for Compared<M> {
+        fn distance(&self, site: &Site, pt: Pt) -> metric::Ratio {
+            self.0.distance(site, pt)
+        }
+
+        fn infinite(&self, sites: &[Site], on_border: &[bool]) -> Vec<bool> {
+            self.0.infinite(sites, on_border)
+        }
+    }
+
+    #[test]
+    fn test_metrics() {
+        let pts = (0 .. 300u32).map(|i| Pt::new((i * 7919 % 97) as u16, (i * 104_729 % 89) as u16)).collect::<Vec<_>>();
+        let area = Area::new(&pts);
+        assert_eq!(Grid::new(&area, &Compared(Manhattan)).owners, Grid::new(&area, &Manhattan).owners);
+        assert_eq!(Grid::new(&area, &Compared(metric::Chebyshev)).owners, Grid::new(&area, &metric::Chebyshev).owners);
+        let unweighted = metric::Weighted::new(metric::Euclidean, vec![1; pts.len()]).unwrap();
+        assert_eq!(Grid::new(&area, &metric::Euclidean).owners, Grid::new(&area, &unweighted).owners);
+
+        let area = Area::new(&[Pt::new(0,0), Pt::new(4,0), Pt::new(2,3)]);
+        let owners = |metric: &str| {
+            let grid = Grid::new(&area, &*metric::parse(metric, 3).unwrap());
+            let cells = grid.owners.iter().map(|o| match o { Owner::Site(id) => area.sites[*id].label, Owner::Tie => '.' }).collect::<Vec<_>>();
+            cells.chunks(grid.width).map(|row| row.iter().collect::<String>()).collect::<Vec<_>>()
+        };
+        // A at 0,0, B at 4,0 and C at 2,3
+        assert_eq!(vec!["AA.BB", "AACBB", "ACCCB", "CCCCC"], owners("manhattan"));
+        assert_eq!(vec!["AA.BB", "AA.BB", ".CCC.", "CCCCC"], owners("chebyshev"));
+        assert_eq!(vec!["AA.BB", "AACBB", "ACCCB", "CCCCC"], owners("euclidean"));
+        assert_eq!(vec!["AACBB", "ACCCB", "CCCCC", "CCCCC"], owners("weighted euclidean 1,1,2"));
+        assert_eq!(vec!["AAA.B", "AAAAB", "AACA.", "ACCCC"], owners("weighted manhattan 3,1,1"));
+
+        assert_eq!(Err("weights must be positive".to_string()), metric::parse("weighted manhattan 1,0,1", 3).map(|_| ()));
+        assert_eq!(Err("weights must be at most 4294967295, got 4294967296".to_string()), metric::parse("weighted euclidean 1,4294967296,1", 3).map(|_| ()));
+        // far apart sites, past what fits the coordinates
+        let far = Area::new(&[Pt::new(0,0), Pt::new(65535,65535)]);
+        assert_eq!(metric::Ratio::whole(131_070), metric::parse("weighted manhattan 1,1", 2).unwrap().distance(&far.sites[0], far.sites[1].coord));
+        assert_eq!(metric::Ratio::new(131_070, 4_294_967_295), metric::parse("weighted manhattan 4294967295,1", 2).unwrap().distance(&far.sites[0], far.sites[1].coord));
+        assert_eq!(Err("expected 3 weights, one per site, but got 2".to_string()), metric::parse("weighted manhattan 1,1", 3).map(|_| ()));
+        assert_eq!(Err("unknown metric taxicab, expected manhattan, chebyshev or euclidean".to_string()), metric::parse("weighted taxicab 1", 1).map(|_| ()));
+    }
+
+    // the sites owning some cells on the border of a much larger window, which for these sites go on to infinity
+    fn far_owners(area: &Area, metric: &dyn Metric, margin: u16) -> Vec<bool> {
+        let (left, top) = (area.top_left.left - margin, area.top_left.top - margin);
+        let (right, bottom) = (area.bottom_right.left + margin, area.bottom_right.top + margin);
+        let border = (left ..= right).flat_map(|x| vec![Pt::new(x, top), Pt::new(x, bottom)])
+            .chain((top ..= bottom).flat_map(|y| vec![Pt::new(left, y), Pt::new(right, y)]));
+        let mut owners = vec![false; area.sites.len()];
+        for pt in border {
+            let distances = area.sites.iter().map(|site| metric.distance(site, pt)).collect::<Vec<_>>();
+            let closest = *distances.iter().min().unwrap();
+            let mut closest_sites = area.sites.iter().filter(|site| distances[site.id] == closest);
+            if let (Some(site), None) = (closest_sites.next(), closest_sites.next()) {
+                owners[site.id] = true;
+            }
+        }
+        owners
+    }
+
+    #[test]
+    fn test_infinite_metrics() {
+        // A, B and C around D, with E in the middle of the edge from A to B
+        let pts = [(0,0), (4,0), (2,3), (2,1), (2,0)].iter().map(|(x, y)| Pt::new(1000 + x, 1000 + y)).collect::<Vec<_>>();
+        let area = Area::new(&pts);
+        let infinite = |metric: &str| {
+            let metric = metric::parse(metric, area.sites.len()).unwrap();
+            let areas = area.analyze(&*metric, 0).finite_areas(&*metric);
+            let infinite = areas.iter().map(|a| a.is_none()).collect::<Vec<_>>();
+            assert_eq!(far_owners(&area, &*metric, 1000), infinite);
+            infinite
+        };
+        assert_eq!(vec![true, true, true, false, true], infinite("euclidean"));
+        assert_eq!(vec![false, false, true, false, false], infinite("weighted euclidean 1,1,2,1,1"));
+        assert_eq!(vec![true, true, false, false, false], infinite("weighted manhattan 2,2,1,1,1"));
+        assert_eq!(vec![true, true, true, false, true], infinite("weighted euclidean 3,3,3,3,3"));
+        assert_eq!(vec![true, true, true, false, false], infinite("chebyshev"));
+        assert_eq!(vec![true, false, true, false, false], infinite("weighted chebyshev 2,1,2,1,1"));
+
+        // sites at the same coordinates own nothing, on a line all the others go on to infinity
+        let area = Area::new(&[Pt::new(1000,1000), Pt::new(1002,1001), Pt::new(1004,1002), Pt::new(1004,1002)]);
+        let metric = metric::Euclidean;
+        let areas = area.analyze(&metric, 0).finite_areas(&metric);
+        assert_eq!(vec![None, None, Some(0), Some(0)], areas);
+        assert_eq!(far_owners(&area, &metric, 1000), areas.iter().map(|a| a.is_none()).collect::<Vec<_>>());
+
+        // scattered sites, some of them at the same coordinates
+        let scattered = Area::new(&(0 .. 60u16).map(|i| Pt::new(1000 + i * 37 % 23, 1000 + i * 53 % 19)).collect::<Vec<_>>());
+        for metric in &[&metric::Chebyshev as &dyn Metric, &metric::Euclidean, &Manhattan] {
+            let infinite = scattered.analyze(*metric, 0).finite_areas(*metric).iter().map(|a| a.is_none()).collect::<Vec<_>>();
+            assert_eq!(far_owners(&scattered, *metric, 1000), infinite);
+        }
+
+        let puzzle = Puzzle6 { coords: pts };
+        assert_eq!(Ok("D at 1002,1001: 3".to_string()), puzzle.query("largest euclidean"));
+        assert_eq!(Err("all regions are infinite".to_string()), Puzzle6 { coords: vec![Pt::new(3,3)] }.query("largest euclidean"));
+    }
+}
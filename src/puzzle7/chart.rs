@@ -0,0 +1,150 @@
+use super::schedule::Solution;
+use super::steps;
+use std::str::FromStr;
+
+#[derive(PartialEq, Debug)]
+pub(super) enum Format {
+    Text,
+    Mermaid,
+    Csv
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "mermaid" => Ok(Format::Mermaid),
+            "csv" => Ok(Format::Csv),
+            _ => Err(format!("unknown format {}, expected text, mermaid or csv", s))
+        }
+    }
+}
+
+// Workers are numbered from 1 in all the formats.
+pub(super) fn timeline(solution: &Solution, format: Format) -> String {
+    match format {
+        Format::Text => gantt(solution),
+        Format::Mermaid => mermaid(solution),
+        Format::Csv => {
+            let mut lines = vec!["worker,step,start,end".to_string()];
+            lines.extend(solution.timeline.iter().map(|w| format!("{},{},{},{}", w.worker + 1, w.step, w.start, w.finish_at)));
+            lines.join("\n")
+        }
+    }
+}
+
+fn workers(solution: &Solution) -> usize {
+    solution.timeline.iter().map(|w| w.worker + 1).max().unwrap_or(0)
+}
+
+// One row per second as in the puzzle, with what each worker is doing (or `.` when idle) and the steps done so far.
+fn gantt(solution: &Solution) -> String {
+    let workers = workers(solution);
+    let width = solution.timeline.iter().map(|w| w.step.len()).chain(Some(format!("Worker {}", workers).len())).max().unwrap();
+    let seconds = solution.time.to_string().len().max(4);
+
+    let mut header = format!("{:<w$}", "Second", w = seconds + 2);
+    for worker in 1 ..= workers {
+        header.push_str(&format!("   {:^w$}", format!("Worker {}", worker), w = width));
+    }
+    header.push_str("   Done");
+
+    let mut lines = vec![header];
+    let mut done = Vec::new();
+    let mut finished = solution.sequence.iter();
+    for second in 0 ..= solution.time {
+        let mut cells = vec!["."; workers];
+        for work in solution.timeline.iter().filter(|w| w.start <= second && second < w.finish_at) {
+            cells[work.worker] = &work.step;
+        }
+        // the sequence is in the order steps finish
+        let finishing = solution.timeline.iter().filter(|w| w.finish_at == second).count();
+        done.extend(finished.by_ref().take(finishing).cloned());
+
+        let mut line = format!("{:>s$}  ", second, s = seconds);
+        for cell in cells {
+            line.push_str(&format!("   {:^w$}", cell, w = width));
+        }
+        line.push_str(&format!("   {}", steps(&done)));
+        lines.push(line.trim_end().to_string());
+    }
+    lines.join("\n")
+}
+
+// A gantt diagram with a section per worker, times are plain seconds.
+fn mermaid(solution: &Solution) -> String {
+    let mut lines = vec![
+        "gantt".to_string(),
+        "    dateFormat X".to_string(),
+        "    axisFormat %s".to_string(),
+    ];
+    for worker in 0 .. workers(solution) {
+        lines.push(format!("    section Worker {}", worker + 1));
+        for work in solution.timeline.iter().filter(|w| w.worker == worker) {
+            lines.push(format!("    {} : {}, {}", work.step, work.start, work.finish_at));
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::cost::{Cost, Letters};
+    use super::super::schedule::{Graph, TieBreak};
+    use super::super::test::example_input;
+
+    fn example() -> Solution {
+        let cost = |step: &str| Letters(0).duration(step).expect("not a single letter step");
+        Graph::new(&example_input()).schedule(2, &cost, TieBreak::Alphabetical).unwrap()
+    }
+
+    #[test]
+    fn test_gantt() {
+        let expected = "Second   Worker 1   Worker 2   Done
+   0        C          .
+   1        C          .
+   2        C          .
+   3        A          F       C
+   4        B          F       CA
+   5        B          F       CA
+   6        D          F       CAB
+   7        D          F       CAB
+   8        D          F       CAB
+   9        D          .       CABF
+  10        E          .       CABFD
+  11        E          .       CABFD
+  12        E          .       CABFD
+  13        E          .       CABFD
+  14        E          .       CABFD
+  15        .          .       CABFDE";
+        assert_eq!(expected, timeline(&example(), Format::Text));
+    }
+
+    #[test]
+    fn test_exports() {
+        let expected = "worker,step,start,end
+1,C,0,3
+1,A,3,4
+2,F,3,9
+1,B,4,6
+1,D,6,10
+1,E,10,15";
+        assert_eq!(expected, timeline(&example(), Format::Csv));
+
+        let expected = "gantt
+    dateFormat X
+    axisFormat %s
+    section Worker 1
+    C : 0, 3
+    A : 3, 4
+    B : 4, 6
+    D : 6, 10
+    E : 10, 15
+    section Worker 2
+    F : 3, 9";
+        assert_eq!(expected, timeline(&example(), Format::Mermaid));
+    }
+}
@@ -0,0 +1,90 @@
+use super::schedule::Graph;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+// How long each step takes, None when the step is unknown to the cost model.
+pub(super) trait Cost {
+    fn duration(&self, step: &str) -> Option<u32>;
+}
+
+// As in the puzzle: single letter steps take their position in the alphabet plus a base cost, e.g. `A` takes base + 1.
+pub(super) struct Letters(pub(super) u32);
+
+impl Cost for Letters {
+    fn duration(&self, step: &str) -> Option<u32> {
+        match step.as_bytes() {
+            [letter @ b'A'..=b'Z'] => Some((letter - b'A') as u32 + 1 + self.0),
+            _ => None
+        }
+    }
+}
+
+impl<F: Fn(&str) -> u32> Cost for F {
+    fn duration(&self, step: &str) -> Option<u32> {
+        Some(self(step))
+    }
+}
+
+// Measured durations of named steps, parsed from `<step> <duration>` lines, lines starting with `#` are comments.
+pub(super) struct Table {
+    durations: BTreeMap<String, u32>
+}
+
+impl FromStr for Table {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut durations = BTreeMap::new();
+        let lines = s.lines().map(|line| line.trim()).filter(|line| !line.is_empty() && !line.starts_with('#'));
+        for line in lines {
+            match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+                [step, duration] => {
+                    let duration = u32::from_str(duration).map_err(|_| format!("invalid duration {} for step {}", duration, step))?;
+                    if durations.insert(step.to_string(), duration).is_some() {
+                        return Err(format!("duplicate duration for step {}", step));
+                    }
+                },
+                _ => return Err(format!("invalid line {}, expected <step> <duration>", line))
+            }
+        }
+        Ok(Table { durations })
+    }
+}
+
+impl Cost for Table {
+    fn duration(&self, step: &str) -> Option<u32> {
+        self.durations.get(step).cloned()
+    }
+}
+
+// The duration of every step of the graph, failing on the first one the cost model doesn't know about.
+pub(super) fn durations(graph: &Graph, cost: &dyn Cost) -> Result<BTreeMap<String, u32>, String> {
+    graph.steps()
+        .map(|step| cost.duration(step).map(|d| (step.clone(), d)).ok_or_else(|| format!("no duration for step {}", step)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_letters() {
+        assert_eq!(Some(1), Letters(0).duration("A"));
+        assert_eq!(Some(86), Letters(60).duration("Z"));
+        assert_eq!(None, Letters(60).duration("a"));
+        assert_eq!(None, Letters(60).duration("AB"));
+    }
+
+    #[test]
+    fn test_table() {
+        let table = Table::from_str("# measured\ncompile 120\n\n  test 45\n").unwrap();
+        assert_eq!(Some(120), table.duration("compile"));
+        assert_eq!(Some(45), table.duration("test"));
+        assert_eq!(None, table.duration("deploy"));
+
+        assert_eq!(Some("invalid duration soon for step deploy".to_string()), Table::from_str("deploy soon").err());
+        assert_eq!(Some("duplicate duration for step test".to_string()), Table::from_str("test 1\ntest 2").err());
+        assert_eq!(Some("invalid line test, expected <step> <duration>".to_string()), Table::from_str("test").err());
+    }
+}
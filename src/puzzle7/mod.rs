@@ -1,45 +1,52 @@
+use std::fs;
 use std::str::FromStr;
 use regex::Regex;
-use std::collections::{HashMap, HashSet, BinaryHeap};
-use std::cmp::Reverse;
 
+mod chart;
+mod cost;
+mod schedule;
+use cost::{Cost, Letters, Table};
+use schedule::{Graph, Solution, TieBreak};
+
+// Steps are named by any run of non whitespace characters, single letters in the puzzle.
 struct Dependency {
-    step: char,
-    before: char
+    step: String,
+    before: String
 }
 
 impl FromStr for Dependency {
-    type Err = ();
+    type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let re = Regex::new(r"^Step (.) must be finished before step (.) can begin.$").unwrap();
-        let caps = re.captures(s).expect("invalid date input");
-        let step = caps[1].chars().next().unwrap();
-        let before = caps[2].chars().next().unwrap();
-        Ok(Dependency { step, before })
+        let re = Regex::new(r"^Step (\S+) must be finished before step (\S+) can begin\.$").unwrap();
+        let caps = re.captures(s).ok_or_else(|| format!("invalid dependency {}", s))?;
+        Ok(Dependency { step: caps[1].to_string(), before: caps[2].to_string() })
     }
 }
 
 pub fn mk(input: String) -> Box<dyn crate::Puzzle> {
-    let deps = input.lines().map(|line| Dependency::from_str(line).expect(format!("invalid line {}", line).as_str())).collect();
+    let deps = input.lines().map(|line| Dependency::from_str(line).unwrap_or_else(|e| panic!("{}", e))).collect();
     Box::new(Puzzle7 { deps })
 }
 
-#[derive(Debug, Clone)]
-struct Step {
-    id: char,
-    depends_on: HashSet<char>
-}
-
-struct Solution {
-    sequence: Vec<char>,
-    time: u32
+// Single letter steps are spelled out as in the puzzle, longer names are separated by spaces.
+fn steps(sequence: &[String]) -> String {
+    if sequence.iter().all(|step| step.chars().count() == 1) {
+        sequence.concat()
+    } else {
+        sequence.join(" ")
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
-struct Work {
-    finish_at: u32, // This needs to come first for ordering
-    step: char,
+// Either the letter rule with the given base cost, or the path to a duration table.
+fn cost(spec: &str) -> Result<Box<dyn Cost>, String> {
+    match u32::from_str(spec) {
+        Ok(base_cost) => Ok(Box::new(Letters(base_cost))),
+        Err(_) => {
+            let table = fs::read_to_string(spec).map_err(|e| format!("cannot read {}: {}", spec, e))?;
+            Ok(Box::new(Table::from_str(&table)?))
+        }
+    }
 }
 
 struct Puzzle7 {
@@ -47,98 +54,61 @@ struct Puzzle7 {
 }
 
 impl Puzzle7 {
-    fn to_steps(&self) -> Vec<Step> {
-        let mut steps = HashMap::new();
-        self.deps.iter().for_each(|x| {
-            steps.entry(x.step).or_insert(Step { id: x.step, depends_on: HashSet::new() });
-            let s = steps.entry(x.before).or_insert(Step {id: x.before, depends_on: HashSet::new() });
-            s.depends_on.insert(x.step);
-        });
-
-        steps.values().cloned().collect()
-    }
-
-    fn cost(base_cost: u32, step: &char) -> u32 {
-        *step as u32 - 'A' as u32 + 1 + base_cost
-    }
-
-    fn solve(&self, workers: u8, base_cost: u32) -> Solution {
-        let mut steps = self.to_steps();
-        let mut steps_ran: HashSet<char> = HashSet::new();
-        let mut run_sequence: Vec<char> = Vec::new();
-        let mut work_queue: BinaryHeap<Reverse<Work>> = BinaryHeap::new();
-        let mut current_time: u32 = 0;
-
-        // TODO: this is pretty disgusting, but here's what's going on
-        //   we first check if anything in the work queue is done (items that have finish_at == current_time)
-        //     anything done is added to steps_ran and run_sequence
-        //   we prune the list of dependencies for the pending steps
-        //   we check for new work (steps that have no dependencies pending)
-        //     fill in the work queue to capacity with available work, marking when each work will finish
-        //   prune the list of pending steps (remove then ones that are running)
-        //   advance the time to when the top item of the work queue will finish
-        //   repeat until there are no outstanding steps left and there are no items in the work queue
-        while !steps.is_empty() || !work_queue.is_empty() {
-            // check for finished work
-            fn clear_finished_work(q: &mut BinaryHeap<Reverse<Work>>, now: u32, finished: &mut Vec<Work>) {
-                match q.peek() {
-                    None => (),
-                    Some(peeked) =>
-                        if peeked.0.finish_at == now {
-                            finished.push(peeked.0);
-                            q.pop();
-                            clear_finished_work(q, now, finished);
-                        }
-                }
-            }
-            let mut finished = Vec::new();
-            clear_finished_work(&mut work_queue, current_time, &mut finished);
-            finished.iter().for_each(|work| {
-                run_sequence.push(work.step);
-                steps_ran.insert(work.step);
-            });
-
-            steps.iter_mut()
-                .for_each(|x| {
-                    x.depends_on.retain(|x| !steps_ran.contains(x) )
-                });
-
-            // find some new work
-            let mut new_steps_to_run = steps.iter()
-                .filter_map(|x| if x.depends_on.is_empty() { Some(Reverse(x.id)) } else { None } )
-                .collect::<BinaryHeap<_>>();
-
-            while work_queue.len() < workers as usize && !new_steps_to_run.is_empty() {
-                let work = new_steps_to_run.pop().unwrap();
-                let finish_at = current_time + Puzzle7::cost(base_cost, &work.0);
-                work_queue.push(Reverse(Work { step: work.0, finish_at }));
-            }
-
-            // remove steps under work
-            steps.retain(|step| work_queue.iter().find(|work| work.0.step == step.id).is_none());
-
-            println!("{}", current_time);
-            steps.iter().for_each(|x| println!("{:?}", x));
-            work_queue.iter().for_each(|x| println!("{:?}", x));
-            println!("{:?}", run_sequence);
-
-            match work_queue.peek() {
-                None => (),
-                Some(Reverse(w)) => current_time = w.finish_at
-            }
-        }
-
-        Solution { sequence: run_sequence, time: current_time }
+    fn solve(&self, workers: u8, cost: &dyn Cost) -> Result<Solution, String> {
+        let graph = Graph::new(&self.deps);
+        let durations = cost::durations(&graph, cost)?;
+        graph.schedule(workers as usize, &|step| durations[step], TieBreak::Alphabetical)
     }
 }
 
 impl crate::Puzzle for Puzzle7 {
     fn part1(&self) -> String {
-        self.solve(1, 0).sequence.iter().collect()
+        steps(&Graph::new(&self.deps).order().unwrap_or_else(|e| panic!("{}", e)))
     }
 
     fn part2(&self) -> String {
-        self.solve(5, 60).time.to_string()
+        self.solve(5, &Letters(60)).unwrap_or_else(|e| panic!("{}", e)).time.to_string()
+    }
+
+    fn query(&self, query: &str) -> Result<String, String> {
+        let graph = Graph::new(&self.deps);
+        // schedules and bounds reject 0 workers themselves
+        let workers = |s: &str| usize::from_str(s).map_err(|_| format!("invalid number of workers {}", s));
+        let durations = |spec: &str| cost(spec).and_then(|cost| cost::durations(&graph, cost.as_ref()));
+        match query.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["schedule", count, spec, tie_break] => {
+                let durations = durations(spec)?;
+                let solution = graph.schedule(workers(count)?, &|step| durations[step], TieBreak::from_str(tie_break)?)?;
+                Ok(format!("{} in {}", steps(&solution.sequence), solution.time))
+            },
+            ["critical", spec] => {
+                let durations = durations(spec)?;
+                let (length, path) = graph.critical_path(&|step| durations[step])?;
+                Ok(format!("{} in {}", steps(&path), length))
+            },
+            ["slack", spec] => {
+                let durations = durations(spec)?;
+                let slack = graph.slack(&|step| durations[step])?;
+                Ok(slack.iter()
+                    .map(|(step, s)| format!("{}: starts between {} and {}, slack {}", step, s.earliest_start, s.latest_start, s.slack()))
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            },
+            ["bounds", count, spec] => {
+                let (workers, durations) = (workers(count)?, durations(spec)?);
+                let lower_bound = graph.lower_bound(workers, &|step| durations[step])?;
+                let scheduled = graph.schedule(workers, &|step| durations[step], TieBreak::Alphabetical)?.time;
+                Ok(format!("at least {}, scheduled in {}", lower_bound, scheduled))
+            },
+            ["timeline", count, spec, tie_break, format] => {
+                let durations = durations(spec)?;
+                let solution = graph.schedule(workers(count)?, &|step| durations[step], TieBreak::from_str(tie_break)?)?;
+                Ok(chart::timeline(&solution, chart::Format::from_str(format)?))
+            },
+            _ => Err(format!("unknown query {}, expected one of: schedule <workers> <cost> <alphabetical|critical>, critical <cost>, slack <cost>, bounds <workers> <cost>, \
+                timeline <workers> <cost> <alphabetical|critical> <text|mermaid|csv>, \
+                where the cost is either the base cost of the letter rule or the path to a table of step durations", query))
+        }
     }
 }
 
@@ -147,15 +117,20 @@ mod test {
     use super::*;
     use crate::Puzzle;
 
-    fn example_input() -> Vec<Dependency> {
+    fn dependency(step: &str, before: &str) -> Dependency {
+        Dependency { step: step.to_string(), before: before.to_string() }
+    }
+
+    // the puzzle's example, shared with the other modules' tests
+    pub(super) fn example_input() -> Vec<Dependency> {
         vec![
-            Dependency {step: 'C', before: 'A'},
-            Dependency {step: 'C', before: 'F'},
-            Dependency {step: 'A', before: 'B'},
-            Dependency {step: 'A', before: 'D'},
-            Dependency {step: 'B', before: 'E'},
-            Dependency {step: 'D', before: 'E'},
-            Dependency {step: 'F', before: 'E'}
+            dependency("C", "A"),
+            dependency("C", "F"),
+            dependency("A", "B"),
+            dependency("A", "D"),
+            dependency("B", "E"),
+            dependency("D", "E"),
+            dependency("F", "E")
         ]
     }
 
@@ -166,8 +141,40 @@ mod test {
 
     #[test]
     fn test_part2() {
-        let solution = Puzzle7 { deps: example_input() }.solve(2, 0);
-        assert_eq!(solution.sequence.iter().collect::<String>(), "CABFDE");
+        let solution = Puzzle7 { deps: example_input() }.solve(2, &Letters(0)).unwrap();
+        assert_eq!(steps(&solution.sequence), "CABFDE");
         assert_eq!(solution.time, 15);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_no_workers() {
+        let puzzle = Puzzle7 { deps: example_input() };
+        assert_eq!(Ok("CABFDE in 15".to_string()), puzzle.query("schedule 2 0 alphabetical"));
+        assert_eq!(Err("at least one worker is needed".to_string()), puzzle.query("schedule 0 0 alphabetical"));
+        assert_eq!(Err("at least one worker is needed".to_string()), puzzle.query("bounds 0 0"));
+        assert_eq!(Some("at least one worker is needed".to_string()), puzzle.solve(0, &Letters(0)).err());
+    }
+
+    #[test]
+    fn test_named_steps() {
+        let input = "Step fetch must be finished before step compile can begin.
+Step fetch must be finished before step lint can begin.
+Step compile must be finished before step test can begin.
+Step lint must be finished before step package can begin.
+Step test must be finished before step package can begin.";
+        let deps = input.lines().map(|line| Dependency::from_str(line).unwrap()).collect();
+        let puzzle = Puzzle7 { deps };
+        assert_eq!(puzzle.part1(), "fetch compile lint test package");
+
+        let table = Table::from_str("fetch 10\ncompile 120\nlint 30\ntest 45\npackage 5").unwrap();
+        let solution = puzzle.solve(2, &table).unwrap();
+        assert_eq!(steps(&solution.sequence), "fetch lint compile test package");
+        assert_eq!(solution.time, 180);
+
+        let solution = puzzle.solve(1, &|step: &str| step.len() as u32).unwrap();
+        assert_eq!(solution.time, 5 + 7 + 4 + 4 + 7);
+
+        assert_eq!(Some("no duration for step compile".to_string()), puzzle.solve(2, &Letters(60)).err());
+        assert_eq!(Some("invalid dependency Step A before B".to_string()), Dependency::from_str("Step A before B").err());
+    }
+}
@@ -0,0 +1,285 @@
+use super::Dependency;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
+use std::str::FromStr;
+
+// Which step to start first when several are available.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum TieBreak {
+    // as in the puzzle
+    Alphabetical,
+    // the step with the longest path to the end first, then alphabetically
+    CriticalPath
+}
+
+impl FromStr for TieBreak {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "alphabetical" => Ok(TieBreak::Alphabetical),
+            "critical" => Ok(TieBreak::CriticalPath),
+            _ => Err(format!("unknown tie break {}, expected alphabetical or critical", s))
+        }
+    }
+}
+
+const NO_WORKERS: &str = "at least one worker is needed";
+
+pub(super) struct Solution {
+    // in the order steps finish
+    pub(super) sequence: Vec<String>,
+    pub(super) time: u32,
+    // in the order steps start, then by worker
+    pub(super) timeline: Vec<Work>
+}
+
+// A step done by a worker (numbered from 0) from `start` until right before `finish_at`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub(super) struct Work {
+    pub(super) finish_at: u32, // This needs to come first for ordering
+    pub(super) step: String,
+    pub(super) worker: usize,
+    pub(super) start: u32
+}
+
+#[derive(Debug, PartialEq)]
+pub(super) struct Slack {
+    pub(super) earliest_start: u32,
+    pub(super) latest_start: u32
+}
+
+impl Slack {
+    // how long the step can be delayed without delaying the whole, with enough workers
+    pub(super) fn slack(&self) -> u32 {
+        self.latest_start - self.earliest_start
+    }
+}
+
+pub(super) struct Graph {
+    // the steps each one depends on
+    before: BTreeMap<String, BTreeSet<String>>,
+    // the steps depending on each one
+    after: BTreeMap<String, BTreeSet<String>>
+}
+
+impl Graph {
+    pub(super) fn new(deps: &[Dependency]) -> Graph {
+        let mut before: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        let mut after: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for dep in deps {
+            before.entry(dep.step.clone()).or_default();
+            after.entry(dep.before.clone()).or_default();
+            before.entry(dep.before.clone()).or_default().insert(dep.step.clone());
+            after.entry(dep.step.clone()).or_default().insert(dep.before.clone());
+        }
+        Graph { before, after }
+    }
+
+    // Runs the steps with the given number of workers, idle workers start the available step with the highest priority.
+    //   Stops early when the remaining steps depend on each other.
+    fn run(&self, workers: usize, cost: &dyn Fn(&str) -> u32, priorities: &BTreeMap<String, u32>) -> Result<Solution, String> {
+        if workers == 0 {
+            return Err(NO_WORKERS.to_string());
+        }
+        let mut pending = self.before.iter().map(|(step, before)| (step, before.len())).collect::<BTreeMap<_, _>>();
+        let mut available = pending.iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(step, _)| (priorities[*step], Reverse(*step)))
+            .collect::<BinaryHeap<_>>();
+        let mut running: BinaryHeap<Reverse<Work>> = BinaryHeap::new();
+        // the lowest numbered idle worker picks the next step
+        let mut idle = (0 .. workers).map(Reverse).collect::<BinaryHeap<_>>();
+        let mut sequence = Vec::new();
+        let mut timeline = Vec::new();
+        let mut time = 0;
+
+        loop {
+            while !idle.is_empty() {
+                match available.pop() {
+                    None => break,
+                    Some((_, Reverse(step))) => {
+                        let Reverse(worker) = idle.pop().unwrap();
+                        let work = Work { finish_at: time + cost(step), step: step.clone(), worker, start: time };
+                        timeline.push(work.clone());
+                        running.push(Reverse(work));
+                    }
+                }
+            }
+            match running.peek() {
+                None => break,
+                Some(Reverse(work)) => time = work.finish_at
+            }
+            // all the work finishing now makes its dependents available before idle workers pick something
+            while let Some(Reverse(work)) = running.peek().cloned().filter(|w| w.0.finish_at == time) {
+                running.pop();
+                idle.push(Reverse(work.worker));
+                for next in self.after[&work.step].iter() {
+                    let count = pending.get_mut(next).unwrap();
+                    *count -= 1;
+                    if *count == 0 {
+                        available.push((priorities[next], Reverse(next)));
+                    }
+                }
+                sequence.push(work.step);
+            }
+        }
+
+        Ok(Solution { sequence, time, timeline })
+    }
+
+    // Checks the run went through all the steps, else finds a cycle among the ones left.
+    fn complete(&self, solution: Solution) -> Result<Solution, String> {
+        if solution.sequence.len() == self.before.len() {
+            return Ok(solution);
+        }
+        // each step left depends on another one left, following them eventually loops
+        let done = solution.sequence.iter().collect::<BTreeSet<_>>();
+        let mut path: Vec<&String> = Vec::new();
+        let mut step = self.before.keys().find(|s| !done.contains(s)).expect("no steps left");
+        while !path.contains(&step) {
+            path.push(step);
+            step = self.before[step].iter().find(|s| !done.contains(s)).expect("step left without dependencies");
+        }
+        let start = path.iter().position(|s| *s == step).unwrap();
+        let mut cycle = path[start ..].to_vec();
+        cycle.push(step);
+        cycle.reverse();
+        Err(format!("steps depend on each other: {}", cycle.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(" -> ")))
+    }
+
+    fn no_priorities(&self) -> BTreeMap<String, u32> {
+        self.before.keys().map(|s| (s.clone(), 0)).collect()
+    }
+
+    pub(super) fn steps(&self) -> impl Iterator<Item = &String> {
+        self.before.keys()
+    }
+
+    pub(super) fn order(&self) -> Result<Vec<String>, String> {
+        self.complete(self.run(1, &|_| 1, &self.no_priorities())?).map(|s| s.sequence)
+    }
+
+    // The longest time from the start of each step to the end of all the steps depending on it.
+    fn tails(&self, cost: &dyn Fn(&str) -> u32) -> Result<BTreeMap<String, u32>, String> {
+        let mut tails = BTreeMap::new();
+        for step in self.order()?.into_iter().rev() {
+            let tail = self.after[&step].iter().map(|next| tails[next]).max().unwrap_or(0);
+            let total = cost(&step) + tail;
+            tails.insert(step, total);
+        }
+        Ok(tails)
+    }
+
+    pub(super) fn schedule(&self, workers: usize, cost: &dyn Fn(&str) -> u32, tie_break: TieBreak) -> Result<Solution, String> {
+        let priorities = match tie_break {
+            TieBreak::Alphabetical => self.no_priorities(),
+            TieBreak::CriticalPath => self.tails(cost)?
+        };
+        self.complete(self.run(workers, cost, &priorities)?)
+    }
+
+    // The longest chain of dependent steps, which takes as long as all the steps with enough workers.
+    pub(super) fn critical_path(&self, cost: &dyn Fn(&str) -> u32) -> Result<(u32, Vec<String>), String> {
+        let tails = self.tails(cost)?;
+        let length = tails.values().cloned().max().unwrap_or(0);
+        let mut path = Vec::new();
+        let mut next = tails.iter().find(|(_, tail)| **tail == length).map(|(step, _)| step);
+        while let Some(step) = next {
+            path.push(step.clone());
+            let rest = tails[step] - cost(step);
+            next = self.after[step].iter().find(|s| tails[*s] == rest && rest > 0);
+        }
+        Ok((length, path))
+    }
+
+    pub(super) fn slack(&self, cost: &dyn Fn(&str) -> u32) -> Result<BTreeMap<String, Slack>, String> {
+        let tails = self.tails(cost)?;
+        let length = tails.values().cloned().max().unwrap_or(0);
+        let mut earliest_finish: BTreeMap<String, u32> = BTreeMap::new();
+        let mut slack = BTreeMap::new();
+        for step in self.order()? {
+            let earliest_start = self.before[&step].iter().map(|s| earliest_finish[s]).max().unwrap_or(0);
+            earliest_finish.insert(step.clone(), earliest_start + cost(&step));
+            let latest_start = length - tails[&step];
+            slack.insert(step, Slack { earliest_start, latest_start });
+        }
+        Ok(slack)
+    }
+
+    // No schedule can be shorter than the critical path, nor than the total work split evenly between workers.
+    pub(super) fn lower_bound(&self, workers: usize, cost: &dyn Fn(&str) -> u32) -> Result<u32, String> {
+        if workers == 0 {
+            return Err(NO_WORKERS.to_string());
+        }
+        let (critical, _) = self.critical_path(cost)?;
+        let total = self.before.keys().map(|s| cost(s)).sum::<u32>();
+        let workers = workers as u32;
+        Ok(critical.max(total.div_ceil(workers)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::cost::{Cost, Letters};
+    use super::super::test::example_input;
+
+    fn graph(deps: &[(&str, &str)]) -> Graph {
+        Graph::new(&deps.iter().map(|(step, before)| Dependency { step: step.to_string(), before: before.to_string() }).collect::<Vec<_>>())
+    }
+
+    fn example() -> Graph {
+        Graph::new(&example_input())
+    }
+
+    fn cost(step: &str) -> u32 {
+        Letters(0).duration(step).expect("not a single letter step")
+    }
+
+    fn names(steps: &[&str]) -> Vec<String> {
+        steps.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_order() {
+        assert_eq!(Ok(names(&["C", "A", "B", "D", "F", "E"])), example().order());
+        let solution = example().schedule(1, &cost, TieBreak::CriticalPath).unwrap();
+        assert_eq!(names(&["C", "F", "A", "D", "B", "E"]), solution.sequence);
+        assert_eq!(21, solution.time);
+    }
+
+    #[test]
+    fn test_cycle() {
+        let cyclic = graph(&[("A", "B"), ("B", "C"), ("C", "D"), ("D", "B"), ("A", "E")]);
+        assert_eq!(Err("steps depend on each other: B -> C -> D -> B".to_string()), cyclic.order());
+        assert!(cyclic.schedule(2, &cost, TieBreak::Alphabetical).is_err());
+        assert!(cyclic.critical_path(&cost).is_err());
+        assert_eq!(Err("steps depend on each other: A -> A".to_string()), graph(&[("A", "A")]).order());
+    }
+
+    #[test]
+    fn test_critical_path() {
+        assert_eq!(Ok((14, names(&["C", "F", "E"]))), example().critical_path(&cost));
+
+        let slack = example().slack(&cost).unwrap();
+        let slacks = slack.iter().map(|(step, s)| (step.as_str(), s.slack())).collect::<Vec<_>>();
+        assert_eq!(vec![("A", 1), ("B", 3), ("C", 0), ("D", 1), ("E", 0), ("F", 0)], slacks);
+        assert_eq!(Slack { earliest_start: 4, latest_start: 7 }, slack["B"]);
+    }
+
+    #[test]
+    fn test_lower_bound() {
+        let example = example();
+        assert_eq!(Ok(21), example.lower_bound(1, &cost));
+        assert_eq!(Ok(14), example.lower_bound(2, &cost));
+        assert_eq!(Ok(14), example.lower_bound(5, &cost));
+        for workers in 1 ..= 5 {
+            let lower_bound = example.lower_bound(workers, &cost).unwrap();
+            assert!(example.schedule(workers, &cost, TieBreak::Alphabetical).unwrap().time >= lower_bound);
+            assert!(example.schedule(workers, &cost, TieBreak::CriticalPath).unwrap().time >= lower_bound);
+        }
+        assert_eq!(Err("at least one worker is needed".to_string()), example.lower_bound(0, &cost));
+        assert_eq!(Some("at least one worker is needed".to_string()), example.schedule(0, &cost, TieBreak::Alphabetical).err());
+    }
+}
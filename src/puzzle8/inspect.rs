@@ -0,0 +1,135 @@
+use super::Node;
+use std::collections::BTreeMap;
+use std::fmt;
+
+// Totals for the nodes at some depth, the root being at depth 0.
+#[derive(Debug, Default, PartialEq)]
+pub(super) struct Level {
+    pub(super) nodes: usize,
+    pub(super) metadata_entries: usize,
+    pub(super) metadata_sum: u64
+}
+
+#[derive(Debug, PartialEq)]
+pub(super) struct Stats {
+    pub(super) nodes: usize,
+    // how many nodes have each number of children
+    pub(super) fan_out: BTreeMap<usize, usize>,
+    pub(super) levels: Vec<Level>
+}
+
+impl Stats {
+    pub(super) fn new(root: &Node) -> Stats {
+        let mut fan_out = BTreeMap::new();
+        let mut levels: Vec<Level> = Vec::new();
+        let mut stack = vec![(root, 0)];
+        while let Some((node, depth)) = stack.pop() {
+            *fan_out.entry(node.children.len()).or_insert(0) += 1;
+            if levels.len() == depth {
+                levels.push(Level::default());
+            }
+            let level = &mut levels[depth];
+            level.nodes += 1;
+            level.metadata_entries += node.metadata.len();
+            level.metadata_sum += node.metadata.iter().map(|m| *m as u64).sum::<u64>();
+            stack.extend(node.children.iter().map(|child| (child, depth + 1)));
+        }
+        Stats { nodes: levels.iter().map(|l| l.nodes).sum(), fan_out, levels }
+    }
+
+    // the number of levels below the root
+    pub(super) fn depth(&self) -> usize {
+        self.levels.len() - 1
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "nodes: {}", self.nodes)?;
+        writeln!(f, "depth: {}", self.depth())?;
+        writeln!(f, "fan-out:")?;
+        for (children, nodes) in self.fan_out.iter() {
+            writeln!(f, "  {} children: {} nodes", children, nodes)?;
+        }
+        write!(f, "levels:")?;
+        for (depth, level) in self.levels.iter().enumerate() {
+            write!(f, "\n  {}: {} nodes, {} metadata entries summing to {}", depth, level.nodes, level.metadata_entries, level.metadata_sum)?;
+        }
+        Ok(())
+    }
+}
+
+// A Graphviz digraph of the tree, nodes being numbered in pre-order.
+//   Each node shows its metadata and value, edges are labelled with the index metadata entries use to refer to the child.
+pub(super) fn dot(root: &Node) -> String {
+    struct Frame<'a> {
+        node: &'a Node,
+        id: usize,
+        values: Vec<u32>
+    }
+
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+    let mut next_id = 1;
+    let mut stack = vec![Frame { node: root, id: 0, values: Vec::new() }];
+    while let Some(frame) = stack.last_mut() {
+        if frame.values.len() < frame.node.children.len() {
+            let child = &frame.node.children[frame.values.len()];
+            edges.push(format!("    n{} -> n{} [label=\"{}\"];", frame.id, next_id, frame.values.len() + 1));
+            stack.push(Frame { node: child, id: next_id, values: Vec::new() });
+            next_id += 1;
+            continue;
+        }
+        let frame = stack.pop().unwrap();
+        let value = frame.node.value_of(&frame.values);
+        let metadata = frame.node.metadata.iter().map(|m| m.to_string()).collect::<Vec<_>>().join(" ");
+        nodes.push((frame.id, format!("    n{} [label=\"metadata: {}\\nvalue: {}\"];", frame.id, metadata, value)));
+        if let Some(parent) = stack.last_mut() {
+            parent.values.push(value);
+        }
+    }
+    nodes.sort();
+
+    let mut lines = vec!["digraph license {".to_string()];
+    lines.extend(nodes.into_iter().map(|(_, line)| line));
+    lines.extend(edges);
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Numbers;
+
+    fn example() -> Node {
+        Node::parse(Numbers::new("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2".as_bytes())).unwrap()
+    }
+
+    #[test]
+    fn test_stats() {
+        let stats = Stats::new(&example());
+        assert_eq!(4, stats.nodes);
+        assert_eq!(2, stats.depth());
+        assert_eq!(vec![(0, 2), (1, 1), (2, 1)], stats.fan_out.into_iter().collect::<Vec<_>>());
+        assert_eq!(vec![
+            Level { nodes: 1, metadata_entries: 3, metadata_sum: 4 },
+            Level { nodes: 2, metadata_entries: 4, metadata_sum: 35 },
+            Level { nodes: 1, metadata_entries: 1, metadata_sum: 99 }
+        ], stats.levels);
+    }
+
+    #[test]
+    fn test_dot() {
+        let expected = r#"digraph license {
+    n0 [label="metadata: 1 1 2\nvalue: 66"];
+    n1 [label="metadata: 10 11 12\nvalue: 33"];
+    n2 [label="metadata: 2\nvalue: 0"];
+    n3 [label="metadata: 99\nvalue: 99"];
+    n0 -> n1 [label="1"];
+    n0 -> n2 [label="2"];
+    n2 -> n3 [label="1"];
+}"#;
+        assert_eq!(expected, dot(&example()));
+    }
+}
@@ -1,14 +1,48 @@
+use std::fs::File;
+use std::io::{self, BufReader, Bytes, Read, Write};
 use std::str::FromStr;
 
-fn parse(input: String) -> Vec<u32> {
-    input.split_ascii_whitespace().map(|x| u32::from_str(x).expect(format!("invalid number {}", x).as_str())).collect()
+mod inspect;
+use inspect::Stats;
+
+// The whitespace separated numbers of a license file, read lazily from any reader.
+struct Numbers<R: Read> {
+    bytes: Bytes<BufReader<R>>
+}
+
+impl<R: Read> Numbers<R> {
+    fn new(reader: R) -> Self {
+        Numbers { bytes: BufReader::new(reader).bytes() }
+    }
 }
+
+impl<R: Read> Iterator for Numbers<R> {
+    type Item = Result<u32, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut token = String::new();
+        for byte in self.bytes.by_ref() {
+            match byte {
+                Err(e) => return Some(Err(format!("cannot read numbers: {}", e))),
+                Ok(b) if b.is_ascii_whitespace() => if !token.is_empty() { break },
+                Ok(b) => token.push(b as char)
+            }
+        }
+        if token.is_empty() {
+            None
+        } else {
+            Some(u32::from_str(&token).map_err(|_| format!("invalid number {}", token)))
+        }
+    }
+}
+
 pub fn mk(input: String) -> Box<dyn crate::Puzzle> {
-    Box::new(Puzzle8 { nodes: parse(input) })
+    let root = Node::parse(Numbers::new(input.as_bytes())).unwrap_or_else(|e| panic!("{}", e));
+    Box::new(Puzzle8 { root })
 }
 
 struct Puzzle8 {
-    nodes: Vec<u32>
+    root: Node
 }
 
 struct Node {
@@ -16,78 +50,223 @@ struct Node {
     children: Vec<Node>
 }
 
-impl Node {
+#[derive(Clone, Copy, PartialEq)]
+enum Order {
+    // a node before its children
+    Pre,
+    // a node after its children
+    Post
+}
 
-    fn new(flat: &mut Vec<u32>) -> Node {
-        flat.reverse();
-        Node::mk_tree(flat)
-    }
+// A node whose header has been read, waiting for its children.
+struct Partial {
+    // position of its header in the input
+    at: usize,
+    n_children: u32,
+    n_meta: u32,
+    children: Vec<Node>
+}
 
-    fn mk_tree(flat: &mut Vec<u32>) -> Node {
-        let n_children = flat.pop().expect("missing number of children");
-        let n_meta = flat.pop().expect("missing number of metadata");
+// The numbers being parsed, along with the position of the next one.
+struct Cursor<I> {
+    numbers: I,
+    position: usize
+}
 
-        let mut children = Vec::new();
-        for _ in 0..n_children {
-            children.push(Node::mk_tree(flat));
+impl<I: Iterator<Item = Result<u32, String>>> Cursor<I> {
+    fn next(&mut self) -> Result<Option<u32>, String> {
+        match self.numbers.next() {
+            None => Ok(None),
+            Some(n) => {
+                self.position += 1;
+                n.map(Some).map_err(|e| format!("{} at position {}", e, self.position - 1))
+            }
         }
-        let mut metadata = Vec::new();
-        for _ in 0..n_meta {
-            let v = flat.pop().expect("missing metadata");
-            metadata.push(v);
+    }
+}
+
+impl Node {
+    // Builds the tree with an explicit stack, so that deeply nested files don't overflow the call stack.
+    //   Nodes are referred to by the position of their header in errors, positions start at 0.
+    fn parse<I: IntoIterator<Item = Result<u32, String>>>(numbers: I) -> Result<Node, String> {
+        let mut cursor = Cursor { numbers: numbers.into_iter(), position: 0 };
+        let mut stack: Vec<Partial> = Vec::new();
+        loop {
+            let at = cursor.position;
+            let n_children = match (cursor.next()?, stack.last()) {
+                (Some(n), _) => n,
+                (None, None) => return Err("missing root node".to_string()),
+                (None, Some(parent)) => return Err(format!("node at {} declares {} children but the input ends after {} of them",
+                                                           parent.at, parent.n_children, parent.children.len()))
+            };
+            let n_meta = cursor.next()?.ok_or_else(|| format!("node at {} is missing its number of metadata", at))?;
+            stack.push(Partial { at, n_children, n_meta, children: Vec::new() });
+
+            // complete the nodes with all their children, until one needs another child
+            while let Some(partial) = stack.pop() {
+                if (partial.children.len() as u32) < partial.n_children {
+                    stack.push(partial);
+                    break;
+                }
+                let mut metadata = Vec::new();
+                for _ in 0 .. partial.n_meta {
+                    match cursor.next()? {
+                        Some(m) => metadata.push(m),
+                        None => return Err(format!("node at {} declares {} metadata entries but the input ends after {} of them",
+                                                   partial.at, partial.n_meta, metadata.len()))
+                    }
+                }
+                let node = Node { metadata, children: partial.children };
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => {
+                        let end = cursor.position;
+                        return match cursor.next()? {
+                            None => Ok(node),
+                            Some(_) => Err(format!("{} extra numbers after the root node, starting at position {}", 1 + cursor.numbers.count(), end))
+                        };
+                    }
+                }
+            }
         }
+    }
 
-        Node { metadata, children }
+    // Writes the tree back to the flat format: each node's header, its children then its metadata.
+    fn serialize<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let mut first = true;
+        let mut write = |out: &mut W, n: u32| {
+            let separator = if first { "" } else { " " };
+            first = false;
+            write!(out, "{}{}", separator, n)
+        };
+        let mut stack = vec![(self, 0)];
+        while let Some((node, next)) = stack.last_mut() {
+            let node: &Node = node;
+            if *next == 0 {
+                write(out, node.children.len() as u32)?;
+                write(out, node.metadata.len() as u32)?;
+            }
+            if *next < node.children.len() {
+                *next += 1;
+                let child = &node.children[*next - 1];
+                stack.push((child, 0));
+            } else {
+                for m in node.metadata.iter() {
+                    write(out, *m)?;
+                }
+                stack.pop();
+            }
+        }
+        Ok(())
     }
 
-    fn value(&self) -> u32 {
-        if self.children.len() == 0 {
+    // The value of a node given the values of its children.
+    fn value_of(&self, children: &[u32]) -> u32 {
+        if children.is_empty() {
             self.metadata.iter().sum()
         } else {
             self.metadata.iter()
                 .map(|m| {
                     let m_size = *m as usize;
-                    if m_size == 0 || m_size > self.children.len() {
+                    if m_size == 0 || m_size > children.len() {
                         0
                     } else {
-                        self.children[m_size - 1].value()
+                        children[m_size - 1]
                     }
                 })
                 .sum()
         }
     }
 
-    fn iter(&self) -> NodeIterator {
-        let a = std::iter::once(self);
-        let more = self.children.iter().flat_map(|x| x.iter());
-        NodeIterator { iter: Box::new(a.chain(more)) }
+    fn value(&self) -> u32 {
+        // in post-order the values of a node's children are the last ones computed
+        let mut values = Vec::new();
+        for node in self.traverse(Order::Post) {
+            let children = values.split_off(values.len() - node.children.len());
+            values.push(node.value_of(&children));
+        }
+        values.pop().unwrap()
+    }
+
+    fn traverse(&self, order: Order) -> Traversal<'_> {
+        Traversal { order, stack: vec![(self, 0)] }
+    }
+
+    fn iter(&self) -> Traversal<'_> {
+        self.traverse(Order::Pre)
     }
 }
 
-// https://amos.me/blog/2019/recursive-iterators-rust/
-struct NodeIterator<'a> {
-    iter: Box<dyn Iterator<Item = &'a Node> + 'a>
+// Dropping nodes one at a time rather than recursively through the children.
+impl Drop for Node {
+    fn drop(&mut self) {
+        let mut stack = std::mem::take(&mut self.children);
+        while let Some(mut node) = stack.pop() {
+            stack.append(&mut node.children);
+        }
+    }
 }
 
-impl<'a> Iterator for NodeIterator<'a> {
+// Depth first traversal, with the nodes being visited and the number of their children already gone through.
+struct Traversal<'a> {
+    order: Order,
+    stack: Vec<(&'a Node, usize)>
+}
+
+impl<'a> Iterator for Traversal<'a> {
     type Item = &'a Node;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.iter.next()
+        match self.order {
+            Order::Pre => {
+                let (node, _) = self.stack.pop()?;
+                self.stack.extend(node.children.iter().rev().map(|child| (child, 0)));
+                Some(node)
+            },
+            Order::Post => loop {
+                let (node, next) = self.stack.last_mut()?;
+                let node: &'a Node = node;
+                if *next < node.children.len() {
+                    *next += 1;
+                    let child = &node.children[*next - 1];
+                    self.stack.push((child, 0));
+                } else {
+                    self.stack.pop();
+                    return Some(node);
+                }
+            }
+        }
     }
 }
 
 impl crate::Puzzle for Puzzle8 {
     fn part1(&self) -> String {
-        let root = Node::new(&mut self.nodes.clone());
-
-        let sum: u32 = root.iter().flat_map(|x| x.metadata.iter()).sum();
+        let sum: u32 = self.root.iter().flat_map(|x| x.metadata.iter()).sum();
         sum.to_string()
     }
 
     fn part2(&self) -> String {
-        let root = Node::new(&mut self.nodes.clone());
-        root.value().to_string()
+        self.root.value().to_string()
+    }
+
+    fn query(&self, query: &str) -> Result<String, String> {
+        match query {
+            "serialize" => {
+                let mut out = Vec::new();
+                self.root.serialize(&mut out).map_err(|e| e.to_string())?;
+                String::from_utf8(out).map_err(|e| e.to_string())
+            },
+            "stats" => Ok(Stats::new(&self.root).to_string()),
+            "dot" => Ok(inspect::dot(&self.root)),
+            _ => match query.split_whitespace().collect::<Vec<_>>().as_slice() {
+                ["check", path] => {
+                    let file = File::open(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+                    let root = Node::parse(Numbers::new(file))?;
+                    Ok(format!("valid, {} nodes", root.iter().count()))
+                },
+                _ => Err(format!("unknown query {}, expected one of: serialize, stats, dot, check <license file>", query))
+            }
+        }
     }
 }
 
@@ -96,8 +275,16 @@ mod test {
     use super::*;
     use crate::Puzzle;
 
+    const EXAMPLE: &str = "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2";
+
     fn example_input() -> Puzzle8 {
-        Puzzle8 { nodes: vec![2, 3, 0, 3, 10, 11, 12, 1, 1, 0, 1, 99, 2, 1, 1, 2] }
+        Puzzle8 { root: Node::parse(Numbers::new(EXAMPLE.as_bytes())).unwrap() }
+    }
+
+    fn serialized(node: &Node) -> String {
+        let mut out = Vec::new();
+        node.serialize(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
     }
 
     #[test]
@@ -109,4 +296,45 @@ mod test {
     fn test_part2() {
         assert_eq!(example_input().part2(), "66");
     }
+
+    #[test]
+    fn test_traversal() {
+        let root = example_input().root;
+        let first_metadata = |order| root.traverse(order).map(|n| n.metadata[0]).collect::<Vec<_>>();
+        assert_eq!(vec![1, 10, 2, 99], first_metadata(Order::Pre));
+        assert_eq!(vec![10, 99, 2, 1], first_metadata(Order::Post));
+    }
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(EXAMPLE, serialized(&example_input().root));
+        let input = "0 1 7\n";
+        assert_eq!("0 1 7", serialized(&Node::parse(Numbers::new(input.as_bytes())).unwrap()));
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        let depth = 1_000_000;
+        let mut flat = [1, 1].repeat(depth);
+        flat.extend(&[0, 1, 5]);
+        flat.extend(vec![1; depth]);
+        let root = Node::parse(flat.iter().map(|n| Ok(*n))).unwrap();
+
+        assert_eq!(depth + 1, root.iter().count());
+        assert_eq!(depth as u32 + 5, root.iter().flat_map(|x| x.metadata.iter()).sum::<u32>());
+        assert_eq!(5, root.value());
+        let expected = flat.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(" ");
+        assert_eq!(expected, serialized(&root));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let parse = |s: &str| Node::parse(Numbers::new(s.as_bytes())).err();
+        assert_eq!(Some("node at 2 declares 2 metadata entries but the input ends after 1 of them".to_string()), parse("1 1 0 2 1"));
+        assert_eq!(Some("node at 0 declares 3 children but the input ends after 1 of them".to_string()), parse("3 1 0 1 5"));
+        assert_eq!(Some("node at 5 is missing its number of metadata".to_string()), parse("2 1 0 1 5 0"));
+        assert_eq!(Some("missing root node".to_string()), parse(" \n"));
+        assert_eq!(Some("invalid number x at position 2".to_string()), parse("0 1 x"));
+        assert_eq!(Some("3 extra numbers after the root node, starting at position 3".to_string()), parse("0 1 7 0 1 7"));
+    }
 }
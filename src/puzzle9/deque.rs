@@ -0,0 +1,49 @@
+use super::{Circle, Rules, Turn};
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter, Error};
+use termion::color;
+
+// The circle going clockwise from front to back, rotated so that the current marble is always at the back.
+//   Moving around the circle is a rotation of the deque, and marbles are inserted and removed at the back.
+pub(super) struct Board {
+    rules: Rules,
+    marbles: VecDeque<u32>
+}
+
+impl Circle for Board {
+    fn new(rules: Rules) -> Board {
+        Board { rules, marbles: VecDeque::from(vec![0]) }
+    }
+
+    fn turn(&mut self, value: u32) -> Turn {
+        let len = self.marbles.len();
+        if value % self.rules.modulus == 0 {
+            // the marble counter-clockwise ends up at the back, the one clockwise of it becomes the current marble
+            self.marbles.rotate_right(self.rules.back % len);
+            let score = self.marbles.pop_back().expect("no more marbles");
+            self.marbles.rotate_left(1 % (len - 1));
+            Turn::Points(value as u64 + score as u64)
+        } else {
+            // right before the marble `insert` clockwise of the current one
+            self.marbles.rotate_left((self.rules.insert - 1) % len);
+            self.marbles.push_back(value);
+            Turn::NoPoints
+        }
+    }
+}
+
+// Clockwise from marble 0 as in the puzzle, or from the lowest marble left when some rules removed it.
+impl Display for Board {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        let start = (0 .. self.marbles.len()).min_by_key(|idx| self.marbles[*idx]).expect("no marbles");
+        let current = self.marbles.len() - 1;
+        for idx in (start .. self.marbles.len()).chain(0 .. start) {
+            if idx == current {
+                write!(f, "{}({}){}", color::Fg(color::LightCyan), self.marbles[idx], color::Fg(color::Reset))?;
+            } else {
+                write!(f, " {} ", self.marbles[idx])?;
+            }
+        }
+        Ok(())
+    }
+}
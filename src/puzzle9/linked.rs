@@ -0,0 +1,110 @@
+use super::{Circle, Rules, Turn};
+use std::fmt::{Display, Formatter, Error};
+use termion::color;
+
+// The original engine: a doubly linked list of marbles in a Vec, kept to cross-check the deque.
+#[derive(Clone, Debug)]
+struct Marble {
+    value: u32, // the marble's value
+    idx: usize, // vector index of this marble
+    next: usize, // vector index of the next marble
+    prev: usize // vector index of the previous
+}
+
+pub(super) struct Board {
+    rules: Rules,
+    current_marble: Marble,
+    marbles: Vec<Marble>
+}
+
+impl Board {
+
+    fn remove_current(&mut self) -> u32 {
+        let value = self.current_marble.value;
+
+        {
+            let prev = self.marbles.get_mut(self.current_marble.prev).expect("no previous marble");
+            prev.next = self.current_marble.next;
+        }
+        {
+            let next = self.marbles.get_mut(self.current_marble.next).expect("no next marble");
+            next.prev = self.current_marble.prev;
+            self.current_marble = next.clone();
+        }
+
+
+        value
+    }
+
+    fn insert(&mut self, value: u32) {
+        let new_idx = self.marbles.len(); // 1
+        let new_marble = Marble { value, idx: new_idx, prev: self.current_marble.prev, next: self.current_marble.idx };
+        self.marbles.push(new_marble.clone());
+
+        {
+            let prev = self.marbles.get_mut(self.current_marble.prev).expect("no previous marble");
+            if new_idx == 1 {
+                prev.prev = new_idx;
+            }
+            prev.next = new_idx; // 1
+        }
+
+        {
+            let next = self.marbles.get_mut(self.current_marble.idx).expect("no previous marble");
+            next.prev = new_idx;
+        }
+
+        self.current_marble = new_marble;
+    }
+}
+
+impl Circle for Board {
+    fn new(rules: Rules) -> Board {
+        let first_marble = Marble { value: 0, idx: 0, next: 0, prev: 0 };
+        Board { rules, current_marble: first_marble.clone(), marbles: vec![first_marble] }
+    }
+
+    fn turn(&mut self, value: u32) -> Turn {
+        if value % self.rules.modulus == 0 {
+            for _ in 0..self.rules.back {
+                self.current_marble = self.marbles.get(self.current_marble.prev).expect("no more marbles").clone();
+            }
+            let score = self.remove_current();
+            Turn::Points(value as u64 + score as u64)
+        }
+        else {
+            for _ in 0..self.rules.insert {
+                self.current_marble = self.marbles.get(self.current_marble.next).expect("no more marbles").clone();
+            }
+            self.insert(value);
+            Turn::NoPoints
+        }
+    }
+}
+
+// Clockwise from the lowest marble, which is marble 0 unless some rules removed it.
+impl Display for Board {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        let mut start = self.current_marble.idx;
+        let mut index = self.marbles[start].next;
+        while index != self.current_marble.idx {
+            if self.marbles[index].value < self.marbles[start].value {
+                start = index;
+            }
+            index = self.marbles[index].next;
+        }
+
+        index = start;
+        while {
+            let marble = self.marbles.get(index).expect("no marbles");
+            if index == self.current_marble.idx {
+                write!(f, "{}({}){}", color::Fg(color::LightCyan), marble.value, color::Fg(color::Reset))?;
+            } else {
+                write!(f, " {} ", marble.value)?;
+            }
+            index = marble.next;
+            index != start
+        } {}
+        Ok(())
+    }
+}
@@ -1,154 +1,154 @@
 use regex::Regex;
 use std::str::FromStr;
-use std::collections::BinaryHeap;
-use std::cmp::Reverse;
 use std::fmt::{Display, Formatter, Error};
-use termion::color;
+
+mod deque;
+mod linked;
 
 #[derive(PartialEq)]
 enum Turn {
-    GameOver,
     NoPoints,
-    Points(u32)
+    Points(u64)
 }
 
-#[derive(Clone, Debug)]
-struct Marble {
-    value: u32, // the marble's value
-    idx: usize, // vector index of this marble
-    next: usize, // vector index of the next marble
-    prev: usize // vector index of the previous
+// When a marble scores and where the others go, the puzzle's rules being the default.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Rules {
+    // marbles multiple of this score instead of being placed
+    modulus: u32,
+    // scoring also removes the marble this many steps counter-clockwise from the current one
+    back: usize,
+    // other marbles are placed this many steps clockwise from the current one
+    insert: usize
 }
 
-struct Board {
-    current_marble: Marble,
-    marbles: Vec<Marble>,
-    remaining_marbles: BinaryHeap<Reverse<u32>>
+impl Rules {
+    fn new(modulus: u32, back: usize, insert: usize) -> Result<Rules, String> {
+        // with a single marble between scores, there's always one left in the circle
+        if modulus < 2 {
+            return Err(format!("the modulus must be at least 2, got {}", modulus));
+        }
+        if insert < 1 {
+            return Err("marbles must be placed at least 1 step clockwise".to_string());
+        }
+        Ok(Rules { modulus, back, insert })
+    }
 }
 
-impl Board {
+impl Default for Rules {
+    fn default() -> Self {
+        Rules { modulus: 23, back: 7, insert: 2 }
+    }
+}
 
-    fn remove_current(&mut self) -> u32 {
-        let value = self.current_marble.value;
-
-        {
-            let mut prev = self.marbles.get_mut(self.current_marble.prev).expect("no previous marble");
-            prev.next = self.current_marble.next;
-        }
-        {
-            let mut next = self.marbles.get_mut(self.current_marble.next).expect("no next marble");
-            next.prev = self.current_marble.prev;
-            self.current_marble = next.clone();
-        }
+// The circle of marbles, starting with marble 0 as the current one.
+//   Displayed clockwise with the current marble highlighted.
+trait Circle: Display {
+    fn new(rules: Rules) -> Self;
 
+    // Places the marble with the given value, which may score instead.
+    fn turn(&mut self, value: u32) -> Turn;
+}
 
-        value
-    }
+// Which circle to play on, the linked list being the original implementation.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Engine {
+    Deque,
+    Linked
+}
 
-    fn insert(&mut self, value: u32) {
-        let new_idx = self.marbles.len(); // 1
-        let new_marble = Marble { value, idx: new_idx, prev: self.current_marble.prev, next: self.current_marble.idx };
-        self.marbles.push(new_marble.clone());
+impl FromStr for Engine {
+    type Err = String;
 
-        {
-            let mut prev = self.marbles.get_mut(self.current_marble.prev).expect("no previous marble");
-            if new_idx == 1 {
-                prev.prev = new_idx;
-            }
-            prev.next = new_idx; // 1
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "deque" => Ok(Engine::Deque),
+            "linked" => Ok(Engine::Linked),
+            _ => Err(format!("unknown engine {}, expected deque or linked", s))
         }
+    }
+}
 
-        {
-            let mut next = self.marbles.get_mut(self.current_marble.idx).expect("no previous marble");
-            next.prev = new_idx;
-        }
+#[derive(Clone, Default, PartialEq, Debug)]
+struct Player {
+    score: u64,
+    scoring_turns: u32
+}
 
-        self.current_marble = new_marble.clone();
-    }
+// Players are numbered from 0, the winner being the first one with the highest score.
+#[derive(PartialEq, Debug)]
+struct Outcome {
+    players: Vec<Player>,
+    winner: usize
+}
 
-    fn turn(&mut self) -> Turn {
-        match self.remaining_marbles.pop() {
-            None => Turn::GameOver,
-            Some(Reverse(value)) => {
-                if value % 23 == 0 {
-                    for _ in 0..7 {
-                        self.current_marble = self.marbles.get(self.current_marble.prev).expect("no more marbles").clone();
-                    }
-                    let score = self.remove_current();
-                    Turn::Points(value + score)
-                }
-                else {
-                    for _ in 0..2 {
-                        self.current_marble = self.marbles.get(self.current_marble.next).expect("no more marbles").clone();
-                    }
-                    self.insert(value);
-                    Turn::NoPoints
-                }
-            }
-        }
+impl Outcome {
+    fn high_score(&self) -> u64 {
+        self.players[self.winner].score
     }
 }
 
-impl Display for Board {
+impl Display for Outcome {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-        let mut index = 0;
-        while {
-            let marble = self.marbles.get(index).expect("no marbles");
-            if index == self.current_marble.idx {
-                write!(f, "{}({}){}", color::Fg(color::LightCyan), marble.value, color::Fg(color::Reset))?;
-            } else {
-                write!(f, " {} ", marble.value)?;
-            }
-            index = marble.next;
-            index != 0 as usize
-        } {}
+        write!(f, "winner: player {} with {} points", self.winner + 1, self.high_score())?;
+        for (idx, player) in self.players.iter().enumerate() {
+            write!(f, "\nplayer {}: {} points in {} scoring turns", idx + 1, player.score, player.scoring_turns)?;
+        }
         Ok(())
     }
 }
 
-struct Game {
+const NO_PLAYERS: &str = "the game needs at least one player";
+
+struct Game<C: Circle> {
     current_player: usize,
-    scores: Vec<u32>,
-    board: Board
+    players: Vec<Player>,
+    next_marble: u32,
+    highest_marble: u32,
+    board: C,
+    // the circle after each turn, as in the puzzle
+    log: Option<Vec<String>>
 }
 
-impl Game {
+impl<C: Circle> Game<C> {
 
-    fn new(n_players: u32, highest_marble: u32) -> Game {
-        let mut remaining_marbles = BinaryHeap::new();
-        for m in 1..=highest_marble {
-            remaining_marbles.push(Reverse(m));
+    fn new(n_players: u32, highest_marble: u32, rules: Rules) -> Result<Game<C>, String> {
+        if n_players == 0 {
+            return Err(NO_PLAYERS.to_string());
         }
+        Ok(Game { current_player: 0, players: vec![Player::default(); n_players as usize], next_marble: 1, highest_marble, board: C::new(rules), log: None })
+    }
 
-        let mut marbles = Vec::new();
-        let first_marble = Marble { value: 0, idx: 0, next: 0, prev: 0 };
-        marbles.push(first_marble.clone());
-        let mut board = Board { current_marble: first_marble, marbles, remaining_marbles };
-
-        let mut scores = Vec::new();
-        (0..n_players).for_each(|_| scores.push(0));
-
-        board.turn(); // play the first turn
-        Game { current_player: 0, scores: scores, board: board }
+    fn logged(n_players: u32, highest_marble: u32, rules: Rules) -> Result<Game<C>, String> {
+        let mut game = Game::new(n_players, highest_marble, rules)?;
+        game.log = Some(vec![format!("[-] {}", game.board)]);
+        Ok(game)
     }
 
     // Rust doesn't have tail call optimization, so this is a loop instead of a recursive call.
-    fn play(&mut self) -> Vec<u32> {
-//        println!("{}", self.board);
-//        println!("{:?}", self.board.marbles);
-        self.current_player = (self.current_player + 1) % self.scores.len();
-        let mut result = self.board.turn();
-        while result != Turn::GameOver {
-//            println!("{}", self.board);
-//            println!("{:?}", self.board.marbles);
-            if let Turn::Points(pts) = result {
-                let score = self.scores.get_mut(self.current_player).expect("unexpected missing score");
-                *score = *score + pts;
+    fn play(&mut self) -> Outcome {
+        while self.next_marble <= self.highest_marble {
+            if let Turn::Points(pts) = self.board.turn(self.next_marble) {
+                let player = self.players.get_mut(self.current_player).expect("unexpected missing player");
+                player.score += pts;
+                player.scoring_turns += 1;
             }
-            self.current_player = (self.current_player + 1) % self.scores.len();
-            result = self.board.turn();
+            if let Some(log) = self.log.as_mut() {
+                log.push(format!("[{}] {}", self.current_player + 1, self.board));
+            }
+            self.next_marble += 1;
+            self.current_player = (self.current_player + 1) % self.players.len();
         }
-        self.scores.clone()
+        // games have at least one player, who wins unless someone after them scores more
+        let winner = (1 .. self.players.len()).fold(0, |winner, idx| if self.players[idx].score > self.players[winner].score { idx } else { winner });
+        Outcome { players: self.players.clone(), winner }
+    }
+}
+
+fn play(engine: Engine, n_players: u32, highest_marble: u32, rules: Rules) -> Result<Outcome, String> {
+    match engine {
+        Engine::Deque => Game::<deque::Board>::new(n_players, highest_marble, rules).map(|mut game| game.play()),
+        Engine::Linked => Game::<linked::Board>::new(n_players, highest_marble, rules).map(|mut game| game.play())
     }
 }
 
@@ -168,18 +168,45 @@ struct Puzzle9 {
 
 impl crate::Puzzle for Puzzle9 {
     fn part1(&self) -> String {
-        let mut game = Game::new(self.n_players, self.highest_marble);
-        let mut scores = game.play();
-        scores.sort();
-        scores.last().expect("no players").to_string()
+        play(Engine::Deque, self.n_players, self.highest_marble, Rules::default()).unwrap_or_else(|e| panic!("{}", e)).high_score().to_string()
     }
 
     fn part2(&self) -> String {
-        // TODO: This problem can probably be solved with math instead of data structures... This is slow.
-        let mut game = Game::new(self.n_players, self.highest_marble * 100);
-        let mut scores = game.play();
-        scores.sort();
-        scores.last().expect("no players").to_string()
+        play(Engine::Deque, self.n_players, self.highest_marble * 100, Rules::default()).unwrap_or_else(|e| panic!("{}", e)).high_score().to_string()
+    }
+
+    fn query(&self, query: &str) -> Result<String, String> {
+        let number = |s: &str| u32::from_str(s).map_err(|_| format!("invalid number {}", s));
+        let players = |s: &str| number(s).and_then(|n| if n == 0 { Err(NO_PLAYERS.to_string()) } else { Ok(n) });
+        let rules = |rules: &[&str]| match rules {
+            [] => Ok(Rules::default()),
+            [modulus, back, insert] => Rules::new(number(modulus)?, number(back)? as usize, number(insert)? as usize),
+            _ => Err(format!("invalid rules {}, expected <modulus> <back> <insert>", rules.join(" ")))
+        };
+        match query.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["play", engine, n_players, highest_marble, custom @ ..] => {
+                let outcome = play(Engine::from_str(engine)?, players(n_players)?, number(highest_marble)?, rules(custom)?)?;
+                Ok(outcome.to_string())
+            },
+            ["log", n_players, highest_marble, custom @ ..] => {
+                let mut game = Game::<deque::Board>::logged(players(n_players)?, number(highest_marble)?, rules(custom)?)?;
+                game.play();
+                Ok(game.log.unwrap_or_default().join("\n"))
+            },
+            // both engines should agree on every player's score
+            ["compare", n_players, highest_marble, custom @ ..] => {
+                let (n_players, highest_marble, rules) = (players(n_players)?, number(highest_marble)?, rules(custom)?);
+                let deque = play(Engine::Deque, n_players, highest_marble, rules)?;
+                let linked = play(Engine::Linked, n_players, highest_marble, rules)?;
+                match deque.players.iter().zip(linked.players.iter()).position(|(d, l)| d != l) {
+                    None => Ok(format!("engines agree, high score {}", deque.high_score())),
+                    Some(player) => Err(format!("engines disagree on player {}: {} with the deque, {} with the linked list",
+                                                player + 1, deque.players[player].score, linked.players[player].score))
+                }
+            },
+            _ => Err(format!("unknown query {}, expected one of: play <deque|linked> <players> <last marble>, log <players> <last marble>, \
+                compare <players> <last marble>, optionally followed by custom rules: <modulus> <back> <insert>", query))
+        }
     }
 }
 
@@ -187,6 +214,7 @@ impl crate::Puzzle for Puzzle9 {
 mod test {
     use super::*;
     use crate::Puzzle;
+    use termion::color;
 
     #[test]
     fn part1() {
@@ -197,4 +225,61 @@ mod test {
         assert_eq!(Puzzle9 { n_players: 21, highest_marble: 6111 }.part1(), "54718");
         assert_eq!(Puzzle9 { n_players: 30, highest_marble: 5807 }.part1(), "37305");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn engines() {
+        for (n_players, highest_marble) in &[(9, 25), (10, 1618), (13, 7999), (1, 100), (7, 22), (411, 5000)] {
+            assert_eq!(play(Engine::Linked, *n_players, *highest_marble, Rules::default()).unwrap(), play(Engine::Deque, *n_players, *highest_marble, Rules::default()).unwrap());
+        }
+        for rules in &[Rules::new(2, 0, 1), Rules::new(5, 3, 4), Rules::new(11, 30, 9), Rules::new(23, 7, 2)] {
+            let rules = rules.clone().unwrap();
+            assert_eq!(play(Engine::Linked, 7, 2000, rules).unwrap(), play(Engine::Deque, 7, 2000, rules).unwrap());
+        }
+    }
+
+    #[test]
+    fn board() {
+        let mut linked = <linked::Board as Circle>::new(Rules::default());
+        let mut deque = <deque::Board as Circle>::new(Rules::default());
+        for marble in 1 ..= 25 {
+            linked.turn(marble);
+            deque.turn(marble);
+            assert_eq!(linked.to_string(), deque.to_string());
+        }
+        let expected = format!(" 0  16  8  17  4  18  19  2  24  20 {}(25){} 10  21 ", color::Fg(color::LightCyan), color::Fg(color::Reset));
+        assert!(deque.to_string().starts_with(&expected));
+    }
+
+    #[test]
+    fn outcome() {
+        let outcome = play(Engine::Deque, 9, 25, Rules::default()).unwrap();
+        assert_eq!(4, outcome.winner);
+        assert_eq!(32, outcome.high_score());
+        assert_eq!(vec![(4, 32)], outcome.players.iter().enumerate().filter(|(_, p)| p.scoring_turns > 0).map(|(idx, p)| (idx, p.score)).collect::<Vec<_>>());
+
+        // ties go to the first player
+        let outcome = play(Engine::Deque, 3, 20, Rules::default()).unwrap();
+        assert_eq!(0, outcome.high_score());
+        assert_eq!(0, outcome.winner);
+
+        assert_eq!(Err("the modulus must be at least 2, got 1".to_string()), Rules::new(1, 7, 2));
+
+        let puzzle = Puzzle9 { n_players: 9, highest_marble: 25 };
+        assert_eq!(Some("the game needs at least one player".to_string()), play(Engine::Linked, 0, 25, Rules::default()).err());
+        for query in &["play deque 0 25", "log 0 25", "compare 0 25 23 7 2"] {
+            assert_eq!(Err("the game needs at least one player".to_string()), puzzle.query(query));
+        }
+        assert!(puzzle.query("play linked 1 25").unwrap().starts_with("winner: player 1 with 32 points"));
+    }
+
+    #[test]
+    fn log() {
+        let mut game = Game::<deque::Board>::logged(9, 25, Rules::default()).unwrap();
+        game.play();
+        let log = game.log.unwrap();
+        assert_eq!(26, log.len());
+        assert_eq!(format!("[-] {}(0){}", color::Fg(color::LightCyan), color::Fg(color::Reset)), log[0]);
+        assert_eq!(format!("[5]  0  16  8  17  4  18 {}(19){} 2  20  10  21  5  22  11  1  12  6  13  3  14  7  15 ",
+                           color::Fg(color::LightCyan), color::Fg(color::Reset)), log[23]);
+    }
+}